@@ -0,0 +1,16 @@
+#!/bin/bash
+set -e
+
+# Builds the released controller and dtoken, upgrade tests migrate their state to the current layout.
+# Needed once before running the sim tests, the released code doesn't change between builds.
+# Commit of the released contracts, their state layout is ContractV0 in upgrade.rs of both contracts
+RELEASE_COMMIT=141dc82681fb19a31d9b7f76267dcb4a2db0afa7
+BASELINE=${BASELINE:-$RELEASE_COMMIT}
+WORKTREE=$(mktemp -d)
+
+git worktree add --detach "$WORKTREE" "$BASELINE"
+trap 'git worktree remove --force "$WORKTREE"' EXIT
+
+RUSTFLAGS='-C link-arg=-s' cargo build --manifest-path "$WORKTREE/contracts/Cargo.toml" --target wasm32-unknown-unknown --release
+mkdir -p ./res/baseline
+cp "$WORKTREE"/contracts/target/wasm32-unknown-unknown/release/{controller,dtoken}.wasm ./res/baseline/
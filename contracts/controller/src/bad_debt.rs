@@ -0,0 +1,108 @@
+use crate::*;
+
+use near_sdk::{is_promise_success, Promise};
+
+impl Contract {
+    /// Whether the account has no supplies left in any market
+    pub fn has_no_supplies(&self, account: &AccountId) -> bool {
+        self.account_supplies
+            .get(account)
+            .map(|supplies| supplies.values().all(|balance| balance == 0))
+            .unwrap_or(true)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Writes off the borrow of an account which has no supplies left to be liquidated.
+    /// The market covers the shortfall from reserves and socializes the rest among suppliers.
+    pub fn write_off_bad_debt(&mut self, account: AccountId, market: AccountId) -> Promise {
+        self.assert_owner_or_keeper();
+        assert!(
+            self.has_no_supplies(&account),
+            "Account {} still has supplies, it should be liquidated instead",
+            account
+        );
+        assert!(
+            self.get_entity_by_token(ActionType::Borrow, account.clone(), market.clone()) > 0,
+            "Account {} has no borrows in market {}",
+            account,
+            market
+        );
+
+        ext_dtoken::write_off_bad_debt(account.clone(), market.clone(), NO_DEPOSIT, TGAS * 20).then(
+            ext_self::write_off_bad_debt_callback(account, market, env::current_account_id(), NO_DEPOSIT, TGAS * 10),
+        )
+    }
+
+    #[private]
+    pub fn write_off_bad_debt_callback(&mut self, account: AccountId, market: AccountId) {
+        assert!(
+            is_promise_success(),
+            "Failed to write off bad debt of {} in market {}",
+            account,
+            market
+        );
+        // Storage released by the write off isn't refunded to the account
+        self.write_entity_by_token(ActionType::Borrow, account, market, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::register_account;
+
+    fn init() -> (Contract, AccountId) {
+        let mut contract = Contract::new(Config::new(alice(), alice()));
+        let market: AccountId = "dwnear.near".parse().unwrap();
+
+        register_account(&mut contract, &carol());
+        contract.internal_increase_borrows(carol(), market.clone(), U128(100));
+        (contract, market)
+    }
+
+    #[test]
+    fn test_keeper_is_allowed_to_write_off() {
+        let (mut contract, market) = init();
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+        contract.add_keeper(bob());
+        assert_eq!(contract.get_keepers(), vec![bob()]);
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(bob()).build());
+        contract.write_off_bad_debt(carol(), market);
+    }
+
+    #[test]
+    #[should_panic(expected = "This functionality is allowed to be called by admin or keeper only")]
+    fn failed_write_off_not_keeper() {
+        let (mut contract, market) = init();
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(bob()).build());
+        contract.write_off_bad_debt(carol(), market);
+    }
+
+    #[test]
+    #[should_panic(expected = "still has supplies, it should be liquidated instead")]
+    fn failed_write_off_with_supplies() {
+        let (mut contract, market) = init();
+        contract.internal_increase_supplies(carol(), "dusdt.near".parse().unwrap(), U128(1));
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+        contract.write_off_bad_debt(carol(), market);
+    }
+
+    #[test]
+    #[should_panic(expected = "has no borrows in market")]
+    fn failed_write_off_without_borrows() {
+        let (mut contract, _) = init();
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+        contract.write_off_bad_debt(carol(), "dusdt.near".parse().unwrap());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::*;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RepayableBorrow {
+    /// Dtoken address
+    pub market: AccountId,
+
+    /// The most of the borrow which can be repaid by a single liquidation
+    pub max_repay_amount: WBalance,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LiquidatableAccount {
+    /// User Account ID
+    pub account_id: AccountId,
+
+    /// Account health factor, RATIO_DECIMALS is 100%
+    pub health_factor: WRatio,
+
+    /// Borrows which can be repaid by liquidators
+    pub repayable_borrows: Vec<RepayableBorrow>,
+}
+
+impl Contract {
+    /// Keeps the account in the borrowers index while it has any open borrow
+    pub fn update_borrowers_index(&mut self, account: &AccountId) {
+        let has_borrows = self
+            .account_borrows
+            .get(account)
+            .map(|borrows| borrows.values().any(|balance| balance > 0))
+            .unwrap_or(false);
+
+        if has_borrows {
+            self.borrowers.insert(account);
+        } else {
+            self.borrowers.remove(account);
+        }
+    }
+
+    fn get_liquidatable_account(&self, account: AccountId) -> Option<LiquidatableAccount> {
+        let health_factor = self.get_health_factor(account.clone());
+        if health_factor >= RATIO_DECIMALS {
+            return None;
+        }
+
+        let max_close_factor = Ratio::from(self.get_contract_config().max_close_factor);
+        let repayable_borrows = self
+            .account_borrows
+            .get(&account)
+            .map(|borrows| {
+                borrows
+                    .iter()
+                    .filter(|(_, balance)| *balance > 0)
+                    .map(|(market, balance)| RepayableBorrow {
+                        market,
+                        max_repay_amount: U128(math::max_repay_amount(balance, max_close_factor)),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(LiquidatableAccount {
+            account_id: account,
+            health_factor: U128(health_factor),
+            repayable_borrows,
+        })
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_borrowers_count(&self) -> u64 {
+        self.borrowers.len()
+    }
+
+    pub fn get_borrowers(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.borrowers
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .collect()
+    }
+
+    /// Checks `limit` borrowers starting from `from_index` and returns the liquidatable ones,
+    /// so the whole index is walked by increasing from_index by limit until get_borrowers_count.
+    /// Borrow amounts don't include interest accrued on markets since the last position change.
+    pub fn get_liquidatable_accounts(&self, from_index: u64, limit: u64) -> Vec<LiquidatableAccount> {
+        self.borrowers
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|account| self.get_liquidatable_account(account))
+            .collect()
+    }
+
+    /// Adds accounts which had borrows before the borrowers index was introduced
+    pub fn index_borrowers(&mut self, accounts: Vec<AccountId>) {
+        self.assert_owner();
+
+        for account in accounts.iter() {
+            self.update_borrowers_index(account);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+
+    use super::*;
+    use crate::test_utils::register_account;
+
+    fn init() -> (Contract, AccountId, AccountId) {
+        let mut contract = Contract::new(Config::new(alice(), alice()));
+        let borrowing_market: AccountId = "dusdt.near".parse().unwrap();
+        let collateral_market: AccountId = "dwnear.near".parse().unwrap();
+
+        contract.upsert_price(&Price { asset_id: borrowing_market.clone(), value: 1, volatility: 100 });
+        contract.upsert_price(&Price { asset_id: collateral_market.clone(), value: 1, volatility: 100 });
+
+        register_account(&mut contract, &bob());
+        register_account(&mut contract, &carol());
+        (contract, borrowing_market, collateral_market)
+    }
+
+    #[test]
+    fn test_borrowers_index() {
+        let (mut contract, borrowing_market, collateral_market) = init();
+
+        contract.internal_increase_supplies(bob(), collateral_market.clone(), U128(100));
+        assert_eq!(contract.get_borrowers_count(), 0);
+
+        contract.internal_increase_borrows(bob(), borrowing_market.clone(), U128(10));
+        contract.internal_increase_borrows(carol(), borrowing_market.clone(), U128(10));
+        assert_eq!(contract.get_borrowers(0, 10), vec![bob(), carol()]);
+
+        contract.internal_decrease_borrows(bob(), borrowing_market, U128(10));
+        assert_eq!(contract.get_borrowers(0, 10), vec![carol()]);
+    }
+
+    #[test]
+    fn test_get_liquidatable_accounts() {
+        let (mut contract, borrowing_market, collateral_market) = init();
+
+        contract.internal_increase_supplies(bob(), collateral_market.clone(), U128(100));
+        contract.internal_increase_borrows(bob(), borrowing_market.clone(), U128(50));
+        contract.internal_increase_supplies(carol(), collateral_market.clone(), U128(100));
+        contract.internal_increase_borrows(carol(), borrowing_market.clone(), U128(200));
+
+        let accounts = contract.get_liquidatable_accounts(0, 10);
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].account_id, carol());
+        assert_eq!(accounts[0].health_factor, U128(5000));
+        assert_eq!(accounts[0].repayable_borrows[0].market, borrowing_market);
+        assert_eq!(accounts[0].repayable_borrows[0].max_repay_amount, U128(100));
+
+        assert!(contract.get_liquidatable_accounts(0, 1).is_empty());
+        assert_eq!(contract.get_liquidatable_accounts(1, 1).len(), 1);
+    }
+}
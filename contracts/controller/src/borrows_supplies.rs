@@ -1,69 +1,70 @@
 use crate::*;
 use crate::borrows_supplies::ActionType::{Borrow, Supply};
 
-#[derive(Serialize, Deserialize)]
+use near_sdk::IntoStorageKey;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
 #[serde(crate = "near_sdk::serde")]
 pub enum ActionType {
     Supply,
     Borrow,
 }
 
-#[near_bindgen]
-impl Contract {
-    #[private]
-    fn set_entity_by_token(&mut self, action: ActionType, account: AccountId, token_address: AccountId, token_amount: Balance) -> Balance {
-        // Receive ActionType whether its Supply or Borrow so that
-        // it will be doing respective variable configuration
-
-        let (accounts, key_prefix) = self.get_params_by_action_mut(action);
-        let account_entry = accounts.get(&account);
-
-        if let None = account_entry {
-                let mut account_map: UnorderedMap<AccountId, u128> =
-                    UnorderedMap::new(key_prefix);
-                account_map.insert(&token_address, &token_amount);
-                accounts.insert(&account, &account_map);
-        } else {
-            account_entry
-                .unwrap()
-                .insert(&token_address, &token_amount);
-        }
-        return token_amount;
-    }
-
-    pub fn get_entity_by_token(&self, action: ActionType, account: AccountId, token_address: AccountId) -> Balance {
-        let balance: Balance = 0;
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PositionRecord {
+    /// User Account ID
+    pub account_id: AccountId,
 
-        let (accounts, _) = self.get_params_by_action(action);
+    /// Dtoken address
+    pub token_address: AccountId,
 
-        let account_entry = accounts.get(&account);
+    /// Supplies balance
+    pub supplied: WBalance,
 
-        if let None = account_entry {
-            return balance;
-        }
+    /// Borrow balance
+    pub borrowed: WBalance,
+}
 
-        let accounts_map = account_entry.unwrap();
+/// Whether the positions map wasn't created with the account's own prefix, UnorderedMap is serialized
+/// starting with the prefix of its key index
+fn is_legacy_map(account_map: &UnorderedMap<AccountId, Balance>, key_prefix: StorageKeys) -> bool {
+    let serialized = account_map.try_to_vec().unwrap();
+    let key_index_prefix: Vec<u8> = BorshDeserialize::deserialize(&mut serialized.as_slice()).unwrap();
+    key_index_prefix != [key_prefix.into_storage_key(), b"i".to_vec()].concat()
+}
 
-        accounts_map.get(&token_address).unwrap_or(balance)
+impl Contract {
+    /// Whether the account still refers to maps created with the legacy shared prefix
+    pub fn has_legacy_positions(&self, account: &AccountId) -> bool {
+        let account_hash = env::sha256(account.as_bytes());
+        let is_legacy = |map: Option<UnorderedMap<AccountId, Balance>>, key_prefix: StorageKeys| {
+            map.map(|account_map| is_legacy_map(&account_map, key_prefix)).unwrap_or(false)
+        };
+        is_legacy(self.account_supplies.get(account), StorageKeys::AccountSupplies { account_hash: account_hash.clone() })
+            || is_legacy(self.account_borrows.get(account), StorageKeys::AccountBorrows { account_hash })
     }
 
-    fn get_params_by_action(&self, action: ActionType) -> (&LookupMap<AccountId, UnorderedMap<AccountId, Balance>>, StorageKeys) {
-        // return parameters respective to ActionType
-        match action {
-            ActionType::Supply => (&self.account_supplies, StorageKeys::SuppliesToken),
-            ActionType::Borrow => (&self.account_borrows, StorageKeys::BorrowsToken)
+    /// Drops references of the account to maps created with the legacy shared prefix, returns whether any was dropped.
+    /// Shared maps are kept as is, they hold the last written balances of all legacy accounts at once.
+    fn remove_legacy_positions(&mut self, account: &AccountId) -> bool {
+        if !self.has_legacy_positions(account) {
+            return false;
         }
+        self.account_supplies.remove(account);
+        self.account_borrows.remove(account);
+        true
     }
 
-    fn get_params_by_action_mut(&mut self, action: ActionType) -> (&mut LookupMap<AccountId, UnorderedMap<AccountId, Balance>>, StorageKeys) {
-        // return parameters respective to ActionType
-        match action {
-            ActionType::Supply => (&mut self.account_supplies, StorageKeys::SuppliesToken),
-            ActionType::Borrow => (&mut self.account_borrows, StorageKeys::BorrowsToken)
+    /// Records the borrow balance with interest accrued by the market since the last change
+    pub fn sync_borrow_balance(&mut self, account: &AccountId, token_address: &AccountId, borrow_balance: WBalance) {
+        let recorded_borrows = self.get_entity_by_token(Borrow, account.clone(), token_address.clone());
+        if recorded_borrows != Balance::from(borrow_balance) {
+            self.set_entity_by_token(Borrow, account.clone(), token_address.clone(), borrow_balance.into());
         }
     }
 
-    pub fn increase_borrows(
+    pub fn internal_increase_borrows(
         &mut self,
         account: AccountId,
         token_address: AccountId,
@@ -75,7 +76,7 @@ impl Contract {
         self.set_entity_by_token(Borrow, account.clone(), token_address.clone(), increased_borrows);
     }
 
-    pub fn decrease_borrows(
+    pub fn internal_decrease_borrows(
         &mut self,
         account: AccountId,
         token_address: AccountId,
@@ -90,7 +91,7 @@ impl Contract {
         return self.set_entity_by_token(Borrow, account.clone(), token_address.clone(), decreased_borrows);
     }
 
-    pub fn increase_supplies(
+    pub fn internal_increase_supplies(
         &mut self,
         account: AccountId,
         token_address: AccountId,
@@ -99,10 +100,15 @@ impl Contract {
         let existing_supplies = self.get_entity_by_token(Supply, account.clone(), token_address.clone());
         let increased_supplies: Balance = existing_supplies + Balance::from(token_amount);
 
+        // The first supply to a market enables it as collateral, it can be disabled with exit_market
+        if existing_supplies == 0 {
+            self.add_collateral(&account, &token_address);
+        }
+
         self.set_entity_by_token(Supply, account.clone(), token_address.clone(), increased_supplies);
     }
 
-    pub fn decrease_supplies(
+    pub fn internal_decrease_supplies(
         &mut self,
         account: AccountId,
         token_address: AccountId,
@@ -123,6 +129,179 @@ impl Contract {
         );
     }
 
+    /// Rebuilds positions of accounts created by the released contract, which kept positions of all accounts
+    /// under the same storage prefix, applied by governance after the timelock. Balances are expected to be
+    /// reconstructed from dtoken state, every account has to be passed with all of its positions within a single call.
+    pub fn migrate_account_positions(&mut self, positions: Vec<PositionRecord>) {
+        let mut migrated_accounts: Vec<AccountId> = Vec::new();
+        for position in positions.iter() {
+            let account = &position.account_id;
+            if !migrated_accounts.contains(account) {
+                assert!(self.remove_legacy_positions(account), "Positions of {} are already migrated", account);
+                self.register_legacy_account(account);
+                migrated_accounts.push(account.clone());
+                events::emit::config_change("migrate_account_positions", account);
+            }
+
+            // Storage of migrated positions is paid by the contract, supplied markets are used as collateral
+            if Balance::from(position.supplied) > 0 {
+                self.write_entity_by_token(Supply, account.clone(), position.token_address.clone(), position.supplied.into());
+                self.write_collateral(account, &position.token_address);
+            }
+            if Balance::from(position.borrowed) > 0 {
+                self.write_entity_by_token(Borrow, account.clone(), position.token_address.clone(), position.borrowed.into());
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[private]
+    fn set_entity_by_token(&mut self, action: ActionType, account: AccountId, token_address: AccountId, token_amount: Balance) -> Balance {
+        let initial_storage = env::storage_usage();
+        let contract_storage = self.write_entity_by_token(action, account.clone(), token_address, token_amount);
+        self.update_account_storage_except(&account, initial_storage, contract_storage);
+        return token_amount;
+    }
+
+    /// Writes the position without charging the account for storage,
+    /// returns storage allocated for contract-wide entries (market rewards and the borrowers index)
+    #[private]
+    pub(crate) fn write_entity_by_token(&mut self, action: ActionType, account: AccountId, token_address: AccountId, token_amount: Balance) -> i64 {
+        // Receive ActionType whether its Supply or Borrow so that
+        // it will be doing respective variable configuration
+
+        // Maps with the legacy shared prefix are written by governance migration only
+        assert!(!self.has_legacy_positions(&account), "Positions of {} have to be migrated first", account);
+
+        // Rewards are accrued on the balance before the change
+        let balance = self.get_entity_by_token(action, account.clone(), token_address.clone());
+        let mut contract_storage = self.distribute_rewards(&action, &account, &token_address, balance, token_amount);
+
+        let (accounts, key_prefix) = self.get_params_by_action_mut(action, &account);
+        let mut account_map: UnorderedMap<AccountId, Balance> = accounts
+            .get(&account)
+            .unwrap_or_else(|| UnorderedMap::new(key_prefix));
+        account_map.insert(&token_address, &token_amount);
+        // Map length is a part of the stored value, so the map is stored again after every change
+        accounts.insert(&account, &account_map);
+
+        if action == Borrow {
+            let initial_storage = env::storage_usage();
+            self.update_borrowers_index(&account);
+            contract_storage += env::storage_usage() as i64 - initial_storage as i64;
+        }
+        return contract_storage;
+    }
+
+    pub fn get_entity_by_token(&self, action: ActionType, account: AccountId, token_address: AccountId) -> Balance {
+        let balance: Balance = 0;
+
+        let accounts = self.get_params_by_action(action);
+
+        let account_entry = accounts.get(&account);
+
+        if let None = account_entry {
+            return balance;
+        }
+
+        let accounts_map = account_entry.unwrap();
+
+        accounts_map.get(&token_address).unwrap_or(balance)
+    }
+
+    fn get_params_by_action(&self, action: ActionType) -> &LookupMap<AccountId, UnorderedMap<AccountId, Balance>> {
+        // return parameters respective to ActionType
+        match action {
+            ActionType::Supply => &self.account_supplies,
+            ActionType::Borrow => &self.account_borrows
+        }
+    }
+
+    fn get_params_by_action_mut(&mut self, action: ActionType, account: &AccountId) -> (&mut LookupMap<AccountId, UnorderedMap<AccountId, Balance>>, StorageKeys) {
+        // return parameters respective to ActionType, every account gets its own storage prefix
+        let account_hash = env::sha256(account.as_bytes());
+        match action {
+            ActionType::Supply => (&mut self.account_supplies, StorageKeys::AccountSupplies { account_hash }),
+            ActionType::Borrow => (&mut self.account_borrows, StorageKeys::AccountBorrows { account_hash })
+        }
+    }
+
+    /// Position changes are reported by listed markets only, see assert_market_caller
+    pub fn increase_borrows(
+        &mut self,
+        account: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+    ) {
+        self.assert_market_caller(&token_address);
+        self.internal_increase_borrows(account, token_address, token_amount);
+    }
+
+    pub fn decrease_borrows(
+        &mut self,
+        account: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+    ) -> Balance {
+        self.assert_market_caller(&token_address);
+        self.internal_decrease_borrows(account, token_address, token_amount)
+    }
+
+    /// Records the borrow balance of the market, used when the market repaid less than controller approved
+    pub fn sync_borrows(&mut self, account: AccountId, token_address: AccountId, borrow_balance: WBalance) {
+        self.assert_market_caller(&token_address);
+        self.sync_borrow_balance(&account, &token_address, borrow_balance);
+    }
+
+    pub fn increase_supplies(
+        &mut self,
+        account: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+    ) {
+        self.assert_market_caller(&token_address);
+        self.internal_increase_supplies(account, token_address, token_amount);
+    }
+
+    pub fn decrease_supplies(
+        &mut self,
+        account: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+    ) -> Balance {
+        self.assert_market_caller(&token_address);
+        self.internal_decrease_supplies(account, token_address, token_amount)
+    }
+
+    /// Moves supplies along with transferred dtokens, can be called by the market only.
+    /// Refused in case the sender would become liquidatable.
+    pub fn transfer_supplies(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        token_address: AccountId,
+        token_amount: WBalance,
+        check_health: bool,
+    ) {
+        self.assert_market_caller(&token_address);
+
+        self.internal_decrease_supplies(sender_id.clone(), token_address.clone(), token_amount);
+
+        if check_health {
+            let health_factor = self.get_health_factor(sender_id.clone());
+            assert!(
+                health_factor >= RATIO_DECIMALS,
+                "Transfer is not allowed for account {}, health factor would be {}",
+                sender_id,
+                health_factor
+            );
+        }
+
+        self.internal_increase_supplies(receiver_id, token_address, token_amount);
+    }
+
     fn is_withdraw_allowed(
         &mut self,
         account: AccountId,
@@ -140,6 +319,7 @@ impl Contract {
         token_address: AccountId,
         token_amount: WBalance,
     ) -> Balance {
+        self.assert_market_caller(&token_address);
         assert_eq!(
             self.is_withdraw_allowed(
                 account_id.clone(),
@@ -153,26 +333,33 @@ impl Contract {
             Balance::from(token_amount)
         );
 
-        return self.decrease_supplies(account_id, token_address, token_amount);
+        return self.internal_decrease_supplies(account_id, token_address, token_amount);
     }
 
-    #[warn(dead_code)]
-    fn is_borrow_allowed(&mut self, account: AccountId, token_address: AccountId, _token_amount: WBalance) -> bool {
-        let _existing_borrows = self.get_entity_by_token(Borrow, account.clone(), token_address.clone());
+    /// Borrow is allowed while collaterals cover borrows including the new one,
+    /// values are taken with prices of the configured price mode
+    fn is_borrow_allowed(&self, account: AccountId, token_address: AccountId, token_amount: WBalance) -> bool {
+        let price = self
+            .get_risk_price(token_address.clone())
+            .unwrap_or_else(|| env::panic_str(&format!("Price of {} isn't set", token_address)));
 
-        let _existing_supplies = self.get_entity_by_token(Supply, account.clone(), token_address.clone());
-        // TODO add check if allowed  (USD-estimated ACCOUNT SUPPLIES > USD-estimated ACCOUNT BORROWED  * ratio ? (or just 0.8) )
+        let collaterals = self.get_collateral_sum(&account);
+        let borrows = self.get_price_sum(self.account_borrows.get(&account))
+            + math::volatility_adjusted_value(token_amount.into(), price.value, price.volatility);
 
-        // FIXME mock-checking for now
-        return true;
+        self.compute_health_factor(collaterals, borrows) >= RATIO_DECIMALS
     }
 
+    /// Records the new borrow, borrow_balance is the balance on the market side before the borrow
     pub fn make_borrow(
         &mut self,
         account_id: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
+        borrow_balance: WBalance,
     ) {
+        self.assert_market_caller(&token_address);
+        self.sync_borrow_balance(&account_id, &token_address, borrow_balance);
         assert_eq!(
             self.is_borrow_allowed(
                 account_id.clone(),
@@ -185,26 +372,36 @@ impl Contract {
             token_address,
             Balance::from(token_amount)
         );
-       self.increase_borrows(account_id, token_address, token_amount);
+       self.internal_increase_borrows(account_id, token_address, token_amount);
     }
 }
 
 
 #[cfg(test)]
 mod tests {
+    use near_sdk::collections::UnorderedMap;
     use near_sdk::json_types::U128;
     use near_sdk::test_utils::test_env::{alice, bob, carol};
-    use near_sdk::AccountId;
-    use crate::{Config, Contract};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, AccountId, Balance};
+    use crate::{Config, Contract, PositionRecord, StorageKeys};
+    use crate::test_utils::register_account;
+    use general::Price;
 
     use crate::borrows_supplies::ActionType::{Borrow, Supply};
 
     pub fn init_test_env() -> (Contract, AccountId, AccountId) {
         let (owner_account, oracle_account, user_account) = (alice(), bob(), carol());
     
-        let eth_contract = Contract::new(Config { owner_id: owner_account, oracle_account_id: oracle_account });
+        let mut eth_contract = Contract::new(Config::new(owner_account, oracle_account.clone()));
+        register_account(&mut eth_contract, &user_account);
+        register_account(&mut eth_contract, &oracle_account);
     
         let token_address: AccountId = "near".parse().unwrap();
+        eth_contract.add_market(token_address.clone());
+
+        // Positions are changed by the market contract
+        testing_env!(VMContextBuilder::new().predecessor_account_id(token_address.clone()).build());
     
         return (eth_contract, token_address, user_account);
     }
@@ -240,6 +437,51 @@ mod tests {
         assert_eq!(near_contract.get_entity_by_token(Borrow, user_account.clone(), token_address.clone()), 8);
     }
 
+    #[test]
+    fn test_repay_syncs_accrued_borrow() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+
+        near_contract.increase_borrows(user_account.clone(), token_address.clone(), U128(10));
+        // Market accrued 2 tokens of interest, the whole debt is repaid
+        near_contract.repay_borrows(user_account.clone(), token_address.clone(), U128(12), U128(12));
+
+        assert_eq!(near_contract.get_entity_by_token(Borrow, user_account.clone(), token_address.clone()), 0);
+    }
+
+    #[test]
+    fn test_sync_borrows_with_market() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+
+        near_contract.increase_borrows(user_account.clone(), token_address.clone(), U128(10));
+        // The borrow was repaid on the market while controller decreased it by a liquidation
+        near_contract.sync_borrows(user_account.clone(), token_address.clone(), U128(0));
+
+        assert_eq!(near_contract.get_entity_by_token(Borrow, user_account.clone(), token_address.clone()), 0);
+        assert_eq!(near_contract.get_borrowers_count(), 0);
+    }
+
+    #[test]
+    fn test_make_borrow_within_collateral() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+        near_contract.upsert_price(&Price { asset_id: token_address.clone(), value: 2, volatility: 80 });
+        near_contract.increase_supplies(user_account.clone(), token_address.clone(), U128(100));
+
+        near_contract.make_borrow(user_account.clone(), token_address.clone(), U128(100), U128(0));
+
+        assert_eq!(near_contract.get_entity_by_token(Borrow, user_account.clone(), token_address.clone()), 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Borrow operation is not allowed for account carol.near")]
+    fn failed_make_borrow_above_collateral() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+        near_contract.upsert_price(&Price { asset_id: token_address.clone(), value: 2, volatility: 80 });
+        near_contract.increase_supplies(user_account.clone(), token_address.clone(), U128(100));
+
+        // Accrued interest counts as a part of existing borrows
+        near_contract.make_borrow(user_account.clone(), token_address.clone(), U128(50), U128(51));
+    }
+
     #[test]
     fn success_increase_n_decrease_supplies() {
         let (mut near_contract, token_address, user_account) = init_test_env();
@@ -265,4 +507,117 @@ mod tests {
 
         near_contract.decrease_borrows(user_account.clone(), token_address.clone(), U128(20));
     }
+
+    #[test]
+    #[should_panic(expected = "This functionality is allowed to be called by listed market near only")]
+    fn failed_increase_supplies_not_market() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(user_account.clone()).build());
+        near_contract.increase_supplies(user_account, token_address, U128(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "This functionality is allowed to be called by listed market dusdt.near only")]
+    fn failed_increase_borrows_unlisted_market() {
+        let (mut near_contract, _token_address, user_account) = init_test_env();
+        let unlisted_market: AccountId = "dusdt.near".parse().unwrap();
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(unlisted_market.clone()).build());
+        near_contract.increase_borrows(user_account, unlisted_market, U128(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "This functionality is allowed to be called by listed market near only")]
+    fn failed_transfer_supplies_not_market() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+        near_contract.increase_supplies(user_account.clone(), token_address.clone(), U128(10));
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(user_account.clone()).build());
+        near_contract.transfer_supplies(user_account, bob(), token_address, U128(10), false);
+    }
+
+    #[test]
+    fn test_positions_are_isolated_per_account() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+        let another_user_account: AccountId = bob();
+
+        near_contract.increase_supplies(user_account.clone(), token_address.clone(), U128(100));
+        near_contract.increase_supplies(another_user_account.clone(), token_address.clone(), U128(50));
+        near_contract.increase_borrows(another_user_account.clone(), token_address.clone(), U128(20));
+
+        assert_eq!(near_contract.get_entity_by_token(Supply, user_account.clone(), token_address.clone()), 100);
+        assert_eq!(near_contract.get_entity_by_token(Borrow, user_account.clone(), token_address.clone()), 0);
+        assert_eq!(near_contract.get_entity_by_token(Supply, another_user_account.clone(), token_address.clone()), 50);
+        assert_eq!(near_contract.get_entity_by_token(Borrow, another_user_account.clone(), token_address.clone()), 20);
+    }
+
+    fn insert_legacy_positions(near_contract: &mut Contract, account: &AccountId, token_address: &AccountId, supplied: Balance, borrowed: Balance) {
+        // Released contract created maps of all accounts with the same prefix, so the last write wins for everyone
+        let mut supplies: UnorderedMap<AccountId, Balance> = UnorderedMap::new(StorageKeys::SuppliesToken);
+        supplies.insert(token_address, &supplied);
+        near_contract.account_supplies.insert(account, &supplies);
+
+        let mut borrows: UnorderedMap<AccountId, Balance> = UnorderedMap::new(StorageKeys::BorrowsToken);
+        borrows.insert(token_address, &borrowed);
+        near_contract.account_borrows.insert(account, &borrows);
+    }
+
+    fn position(account: &AccountId, token_address: &AccountId, supplied: Balance, borrowed: Balance) -> PositionRecord {
+        PositionRecord {
+            account_id: account.clone(),
+            token_address: token_address.clone(),
+            supplied: U128(supplied),
+            borrowed: U128(borrowed),
+        }
+    }
+
+    #[test]
+    fn test_migrate_account_positions() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+        let another_user_account: AccountId = "dave.near".parse().unwrap();
+
+        insert_legacy_positions(&mut near_contract, &user_account, &token_address, 100, 10);
+        insert_legacy_positions(&mut near_contract, &another_user_account, &token_address, 50, 0);
+        // Both accounts read the balances written last
+        assert_eq!(near_contract.get_entity_by_token(Supply, user_account.clone(), token_address.clone()), 50);
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+        near_contract.migrate_account_positions(vec![position(&user_account, &token_address, 100, 10)]);
+
+        assert_eq!(near_contract.get_entity_by_token(Supply, user_account.clone(), token_address.clone()), 100);
+        assert_eq!(near_contract.get_entity_by_token(Borrow, user_account.clone(), token_address.clone()), 10);
+        assert_eq!(near_contract.get_borrowers(0, 10), vec![user_account.clone()]);
+        assert!(near_contract.is_collateral(&user_account, &token_address));
+
+        // Migration of the first account doesn't touch the one which isn't migrated yet
+        assert_eq!(near_contract.get_entity_by_token(Supply, another_user_account.clone(), token_address.clone()), 50);
+
+        near_contract.migrate_account_positions(vec![position(&another_user_account, &token_address, 50, 0)]);
+
+        assert_eq!(near_contract.get_entity_by_token(Supply, another_user_account.clone(), token_address.clone()), 50);
+        assert_eq!(near_contract.get_entity_by_token(Borrow, another_user_account.clone(), token_address.clone()), 0);
+        assert_eq!(near_contract.get_entity_by_token(Supply, user_account.clone(), token_address.clone()), 100);
+        assert_eq!(near_contract.get_borrowers(0, 10), vec![user_account]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Positions of carol.near have to be migrated first")]
+    fn failed_change_of_legacy_positions() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+        insert_legacy_positions(&mut near_contract, &user_account, &token_address, 100, 10);
+
+        near_contract.increase_supplies(user_account, token_address, U128(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Positions of carol.near are already migrated")]
+    fn failed_migrate_account_positions_twice() {
+        let (mut near_contract, token_address, user_account) = init_test_env();
+        insert_legacy_positions(&mut near_contract, &user_account, &token_address, 100, 10);
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+        near_contract.migrate_account_positions(vec![position(&user_account, &token_address, 100, 10)]);
+        near_contract.migrate_account_positions(vec![position(&user_account, &token_address, 100, 10)]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,160 @@
+use crate::*;
+
+impl Contract {
+    pub fn add_collateral(&mut self, account: &AccountId, token_address: &AccountId) {
+        let initial_storage = env::storage_usage();
+        self.write_collateral(account, token_address);
+        self.update_account_storage(account, initial_storage);
+    }
+
+    /// Adds the collateral without charging the account for storage
+    pub fn write_collateral(&mut self, account: &AccountId, token_address: &AccountId) {
+        let mut collaterals = self.account_collaterals.get(account).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountCollaterals { account_hash: env::sha256(account.as_bytes()) })
+        });
+        collaterals.insert(token_address);
+        self.account_collaterals.insert(account, &collaterals);
+    }
+
+    pub fn is_collateral(&self, account: &AccountId, token_address: &AccountId) -> bool {
+        self.account_collaterals
+            .get(account)
+            .map(|collaterals| collaterals.contains(token_address))
+            .unwrap_or(false)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Enables supplies of the given markets to be used as collateral of the caller
+    pub fn enter_markets(&mut self, markets: Vec<AccountId>) {
+        let account = env::predecessor_account_id();
+        for market in markets.iter() {
+            assert!(self.is_listed_market(market), "Market {} isn't listed", market);
+            self.add_collateral(&account, market);
+        }
+    }
+
+    /// Disables supplies of the market to be used as collateral of the caller.
+    /// Refused in case the account would become liquidatable.
+    pub fn exit_market(&mut self, market: AccountId) {
+        let account = env::predecessor_account_id();
+        let mut collaterals = match self.account_collaterals.get(&account) {
+            Some(collaterals) if collaterals.contains(&market) => collaterals,
+            _ => return,
+        };
+
+        let collateral_sum = self.get_collateral_sum(&account).saturating_sub(self.get_collateral_value(&account, &market));
+        let borrows = self.get_price_sum(self.account_borrows.get(&account));
+        let health_factor = self.compute_health_factor(collateral_sum, borrows);
+
+        assert!(
+            health_factor >= RATIO_DECIMALS,
+            "Exit market {} is not allowed for account {}, health factor would be {}",
+            market,
+            account,
+            health_factor
+        );
+
+        let initial_storage = env::storage_usage();
+        collaterals.remove(&market);
+        self.account_collaterals.insert(&account, &collaterals);
+        self.update_account_storage(&account, initial_storage);
+    }
+
+    pub fn get_account_collaterals(&self, account: AccountId) -> Vec<AccountId> {
+        self.account_collaterals
+            .get(&account)
+            .map(|collaterals| collaterals.to_vec())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob};
+
+    use super::*;
+    use crate::test_utils::register_account;
+
+    fn init() -> (Contract, AccountId) {
+        let (_owner_account, user_account) = (alice(), bob());
+
+        let mut controller_contract = Contract::new(Config::new(user_account.clone(), user_account.clone()));
+        register_account(&mut controller_contract, &user_account);
+
+        let mut prices: Vec<Price> = Vec::new();
+        prices.push(Price {
+            asset_id: AccountId::new_unchecked("wnear.near".to_string()),
+            value: 2,
+            volatility: 100
+        });
+        prices.push(Price {
+            asset_id: AccountId::new_unchecked("weth.near".to_string()),
+            value: 2,
+            volatility: 100
+        });
+
+        for price in prices.iter() {
+            controller_contract.add_market(price.asset_id.clone());
+        }
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 83452949,
+            price_list: prices
+        });
+
+        controller_contract.internal_increase_supplies(
+            user_account.clone(),
+            AccountId::new_unchecked("wnear.near".to_string()),
+            WBalance::from(100)
+        );
+        controller_contract.internal_increase_supplies(
+            user_account.clone(),
+            AccountId::new_unchecked("weth.near".to_string()),
+            WBalance::from(100)
+        );
+
+        return (controller_contract, user_account);
+    }
+
+    #[test]
+    fn test_exit_and_enter_market() {
+        let (mut controller_contract, user_account) = init();
+        let market = AccountId::new_unchecked("weth.near".to_string());
+
+        assert_eq!(controller_contract.get_account_collaterals(user_account.clone()).len(), 2, "Supplied markets should be used as collateral");
+
+        controller_contract.internal_increase_borrows(user_account.clone(), market.clone(), WBalance::from(50));
+        controller_contract.exit_market(market.clone());
+
+        assert_eq!(controller_contract.is_collateral(&user_account, &market), false);
+        assert_eq!(controller_contract.get_health_factor(user_account.clone()), 2 * RATIO_DECIMALS, "Only wnear supplies should count as collateral");
+
+        controller_contract.enter_markets(vec![market.clone()]);
+
+        assert_eq!(controller_contract.is_collateral(&user_account, &market), true);
+        assert_eq!(controller_contract.get_health_factor(user_account.clone()), 4 * RATIO_DECIMALS);
+    }
+
+    #[test]
+    #[should_panic(expected = "Exit market weth.near is not allowed")]
+    fn failed_exit_market_unhealthy() {
+        let (mut controller_contract, user_account) = init();
+
+        controller_contract.internal_increase_borrows(
+            user_account.clone(),
+            AccountId::new_unchecked("weth.near".to_string()),
+            WBalance::from(150)
+        );
+
+        controller_contract.exit_market(AccountId::new_unchecked("weth.near".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Market dusdt.near isn't listed")]
+    fn failed_enter_unlisted_market() {
+        let (mut controller_contract, _user_account) = init();
+
+        controller_contract.enter_markets(vec![AccountId::new_unchecked("dusdt.near".to_string())]);
+    }
+}
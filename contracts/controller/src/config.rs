@@ -8,8 +8,73 @@ pub struct Config {
     pub owner_id: AccountId,
 
     /// The account ID of the controller contract
-    pub oracle_account_id: AccountId
+    pub oracle_account_id: AccountId,
 
+    /// The most of a borrow which can be repaid by a single liquidation, RATIO_DECIMALS is 100%
+    #[serde(default = "default_max_close_factor")]
+    pub max_close_factor: WRatio,
+
+    /// Lowest liquidation discount, RATIO_DECIMALS is 100%
+    #[serde(default = "default_liquidation_discount_floor")]
+    pub liquidation_discount_floor: WRatio,
+
+    /// Highest liquidation discount, RATIO_DECIMALS is 100%
+    #[serde(default = "default_liquidation_discount_cap")]
+    pub liquidation_discount_cap: WRatio,
+
+    /// Share of seized collateral which goes to reserves of the collateral market, RATIO_DECIMALS is 100%
+    #[serde(default = "default_liquidation_fee")]
+    pub liquidation_fee: WRatio,
+
+}
+
+fn default_max_close_factor() -> WRatio {
+    U128(5000)
+}
+
+fn default_liquidation_discount_floor() -> WRatio {
+    U128(200)
+}
+
+fn default_liquidation_discount_cap() -> WRatio {
+    U128(2000)
+}
+
+fn default_liquidation_fee() -> WRatio {
+    U128(300)
+}
+
+impl Config {
+    /// Config with default liquidation parameters
+    pub fn new(owner_id: AccountId, oracle_account_id: AccountId) -> Self {
+        Config {
+            owner_id,
+            oracle_account_id,
+            max_close_factor: default_max_close_factor(),
+            liquidation_discount_floor: default_liquidation_discount_floor(),
+            liquidation_discount_cap: default_liquidation_discount_cap(),
+            liquidation_fee: default_liquidation_fee(),
+        }
+    }
+
+    pub fn assert_valid(&self) {
+        assert!(
+            Ratio::from(self.max_close_factor) > 0 && Ratio::from(self.max_close_factor) <= RATIO_DECIMALS,
+            "Max close factor should be in range (0, 100%]"
+        );
+        assert!(
+            Ratio::from(self.liquidation_discount_floor) <= Ratio::from(self.liquidation_discount_cap),
+            "Liquidation discount floor should be less or equal to the cap"
+        );
+        assert!(
+            Ratio::from(self.liquidation_discount_cap) < RATIO_DECIMALS,
+            "Liquidation discount cap should be less than 100%"
+        );
+        assert!(
+            Ratio::from(self.liquidation_fee) <= RATIO_DECIMALS,
+            "Liquidation fee should be less or equal to 100%"
+        );
+    }
 }
 
 impl Contract {
@@ -18,4 +83,92 @@ impl Contract {
         self.config.get().unwrap()
     }
 
-}
\ No newline at end of file
+    pub fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.get_contract_config().owner_id,
+            "This functionality is allowed to be called by admin only"
+        );
+    }
+
+    pub fn assert_owner_or_keeper(&self) {
+        let account = env::predecessor_account_id();
+        assert!(
+            account == self.get_contract_config().owner_id || self.keepers.contains(&account),
+            "This functionality is allowed to be called by admin or keeper only"
+        );
+    }
+
+    /// Positions of a market can be changed by the listed market contract only
+    pub fn assert_market_caller(&self, market: &AccountId) {
+        assert!(
+            env::predecessor_account_id() == *market && self.is_listed_market(market),
+            "This functionality is allowed to be called by listed market {} only",
+            market
+        );
+    }
+
+    /// Markets priced by the released contract weren't listed, they count as listed
+    /// until the first accepted price moves them to listed markets
+    pub fn is_listed_market(&self, market: &AccountId) -> bool {
+        self.listed_markets.contains(market) || self.prices.contains_key(market)
+    }
+
+    /// Lists the dtoken market, applied by governance after the timelock
+    pub fn add_market(&mut self, market: AccountId) {
+        events::emit::config_change("add_market", &market);
+        self.listed_markets.insert(&market);
+    }
+
+    /// Sets the account allowed to push prices, applied by governance after the timelock
+    pub fn set_oracle_account_id(&mut self, oracle_account_id: AccountId) {
+        let mut config = self.get_contract_config();
+        config.oracle_account_id = oracle_account_id;
+
+        events::emit::config_change("config", &config);
+        self.config.set(&config);
+    }
+
+    /// Sets liquidation limits, applied by governance after the timelock
+    pub fn set_liquidation_config(
+        &mut self,
+        max_close_factor: WRatio,
+        liquidation_discount_floor: WRatio,
+        liquidation_discount_cap: WRatio,
+        liquidation_fee: WRatio,
+    ) {
+        let mut config = self.get_contract_config();
+        config.max_close_factor = max_close_factor;
+        config.liquidation_discount_floor = liquidation_discount_floor;
+        config.liquidation_discount_cap = liquidation_discount_cap;
+        config.liquidation_fee = liquidation_fee;
+        config.assert_valid();
+
+        events::emit::config_change("config", &config);
+        self.config.set(&config);
+    }
+
+    /// Allows the account to write off bad debt, applied by governance after the timelock
+    pub fn add_keeper(&mut self, keeper: AccountId) {
+        events::emit::config_change("add_keeper", &keeper);
+        self.keepers.insert(&keeper);
+    }
+
+    /// Revokes the keeper role, applied by governance after the timelock
+    pub fn remove_keeper(&mut self, keeper: AccountId) {
+        events::emit::config_change("remove_keeper", &keeper);
+        self.keepers.remove(&keeper);
+    }
+
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_keepers(&self) -> Vec<AccountId> {
+        self.keepers.to_vec()
+    }
+
+    pub fn get_markets(&self) -> Vec<AccountId> {
+        self.listed_markets.to_vec()
+    }
+}
@@ -0,0 +1,295 @@
+use crate::*;
+
+use near_sdk::BlockHeight;
+
+/// Delay of risk parameter changes set on initialization, about 2 days of 1 second blocks
+pub const DEFAULT_GOVERNANCE_DELAY: BlockHeight = 2 * 24 * 60 * 60;
+
+/// Risk parameter change, every action is applied by the respective config setter.
+/// Market parameters are set by the call of the market setter which accepts calls of the controller only.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GovernanceAction {
+    SetOracleAccountId { oracle_account_id: AccountId },
+    SetRewardToken { reward_token: AccountId },
+    SetMarketEmission { market: AccountId, supply_per_block: WBalance, borrow_per_block: WBalance },
+    SetLiquidationConfig {
+        max_close_factor: WRatio,
+        liquidation_discount_floor: WRatio,
+        liquidation_discount_cap: WRatio,
+        liquidation_fee: WRatio,
+    },
+    SetPriceMode { mode: PriceMode, twap_window: BlockHeight },
+    SetGovernanceDelay { delay: BlockHeight },
+    AddMarket { market: AccountId },
+    AddKeeper { keeper: AccountId },
+    RemoveKeeper { keeper: AccountId },
+    SetMarketTotals { market: AccountId, total_supplied: WBalance, total_borrowed: WBalance },
+    MigrateAccountPositions { positions: Vec<PositionRecord> },
+    SetInterestRateModel { market: AccountId, model: InterestRateModel },
+    SetFlashLoanFee { market: AccountId, fee: WRatio },
+    SetNativeMarket { market: AccountId, enabled: bool },
+    SetSameBlockGuard { market: AccountId, enabled: bool },
+    AddFlashLoanReceiver { market: AccountId, receiver: AccountId },
+    RemoveFlashLoanReceiver { market: AccountId, receiver: AccountId },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GovernanceProposal {
+    /// Unique proposal id
+    pub id: u64,
+
+    /// Action applied on execution
+    pub action: GovernanceAction,
+
+    /// BlockHeight since which the proposal can be executed
+    pub eta: BlockHeight,
+}
+
+impl GovernanceAction {
+    /// Market setters are called after the proposal is removed, so their params are checked on queueing
+    fn assert_valid(&self) {
+        match self {
+            GovernanceAction::SetInterestRateModel { model, .. } => model.assert_valid(),
+            GovernanceAction::SetFlashLoanFee { fee, .. } => {
+                assert!(Ratio::from(*fee) <= RATIO_DECIMALS, "Flash loan fee should be less or equal to 100%")
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Contract {
+    fn apply_governance_action(&mut self, action: GovernanceAction) {
+        match action {
+            GovernanceAction::SetOracleAccountId { oracle_account_id } => self.set_oracle_account_id(oracle_account_id),
+            GovernanceAction::SetRewardToken { reward_token } => self.set_reward_token(reward_token),
+            GovernanceAction::SetMarketEmission { market, supply_per_block, borrow_per_block } => {
+                self.set_market_emission(market, supply_per_block, borrow_per_block)
+            }
+            GovernanceAction::SetLiquidationConfig {
+                max_close_factor,
+                liquidation_discount_floor,
+                liquidation_discount_cap,
+                liquidation_fee,
+            } => self.set_liquidation_config(
+                max_close_factor,
+                liquidation_discount_floor,
+                liquidation_discount_cap,
+                liquidation_fee,
+            ),
+            GovernanceAction::SetPriceMode { mode, twap_window } => self.set_price_mode(mode, twap_window),
+            GovernanceAction::SetGovernanceDelay { delay } => {
+                events::emit::config_change("governance_delay", &delay);
+                self.governance_delay = delay;
+            }
+            GovernanceAction::AddMarket { market } => self.add_market(market),
+            GovernanceAction::AddKeeper { keeper } => self.add_keeper(keeper),
+            GovernanceAction::RemoveKeeper { keeper } => self.remove_keeper(keeper),
+            GovernanceAction::SetMarketTotals { market, total_supplied, total_borrowed } => {
+                self.set_market_totals(market, total_supplied, total_borrowed)
+            }
+            GovernanceAction::MigrateAccountPositions { positions } => self.migrate_account_positions(positions),
+            GovernanceAction::SetInterestRateModel { market, model } => {
+                ext_dtoken::set_interest_rate_model(model, market, NO_DEPOSIT, TGAS * 10);
+            }
+            GovernanceAction::SetFlashLoanFee { market, fee } => {
+                ext_dtoken::set_flash_loan_fee(fee, market, NO_DEPOSIT, TGAS * 10);
+            }
+            GovernanceAction::SetNativeMarket { market, enabled } => {
+                ext_dtoken::set_native_market(enabled, market, NO_DEPOSIT, TGAS * 10);
+            }
+            GovernanceAction::SetSameBlockGuard { market, enabled } => {
+                ext_dtoken::set_same_block_guard(enabled, market, NO_DEPOSIT, TGAS * 10);
+            }
+            GovernanceAction::AddFlashLoanReceiver { market, receiver } => {
+                ext_dtoken::add_flash_loan_receiver(receiver, market, NO_DEPOSIT, TGAS * 10);
+            }
+            GovernanceAction::RemoveFlashLoanReceiver { market, receiver } => {
+                ext_dtoken::remove_flash_loan_receiver(receiver, market, NO_DEPOSIT, TGAS * 10);
+            }
+        }
+    }
+
+    fn get_proposal(&self, proposal_id: u64) -> GovernanceProposal {
+        self.governance_proposals
+            .get(&proposal_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Proposal {} doesn't exist", proposal_id)))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Queues the action, it can be executed once governance delay has passed
+    pub fn queue_action(&mut self, action: GovernanceAction) -> u64 {
+        self.assert_owner();
+        action.assert_valid();
+
+        let proposal = GovernanceProposal {
+            id: self.next_proposal_id,
+            action,
+            eta: env::block_height() + self.governance_delay,
+        };
+        self.next_proposal_id += 1;
+
+        events::emit::governance(proposal.id, &proposal.action, proposal.eta, "queued");
+        self.governance_proposals.insert(&proposal.id, &proposal);
+        proposal.id
+    }
+
+    pub fn execute_action(&mut self, proposal_id: u64) {
+        self.assert_owner();
+
+        let proposal = self.get_proposal(proposal_id);
+        assert!(
+            env::block_height() >= proposal.eta,
+            "Proposal {} can't be executed before block {}",
+            proposal_id,
+            proposal.eta
+        );
+
+        self.governance_proposals.remove(&proposal_id);
+        events::emit::governance(proposal.id, &proposal.action, proposal.eta, "executed");
+        self.apply_governance_action(proposal.action);
+    }
+
+    pub fn cancel_action(&mut self, proposal_id: u64) {
+        self.assert_owner();
+
+        let proposal = self.get_proposal(proposal_id);
+        self.governance_proposals.remove(&proposal_id);
+        events::emit::governance(proposal.id, &proposal.action, proposal.eta, "cancelled");
+    }
+
+    pub fn get_queued_action(&self, proposal_id: u64) -> Option<GovernanceProposal> {
+        self.governance_proposals.get(&proposal_id)
+    }
+
+    /// Returns queued proposals which are neither executed nor cancelled
+    pub fn get_queued_actions(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<GovernanceProposal> {
+        self.governance_proposals
+            .values()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or_else(|| self.governance_proposals.len()) as usize)
+            .collect()
+    }
+
+    pub fn get_governance_delay(&self) -> BlockHeight {
+        self.governance_delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    fn init() -> Contract {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+        Contract::new(Config::new(alice(), alice()))
+    }
+
+    fn set_block_height(block_height: BlockHeight) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).block_index(block_height).build());
+    }
+
+    #[test]
+    fn test_queue_and_execute_action() {
+        let mut contract = init();
+        let action = GovernanceAction::SetOracleAccountId { oracle_account_id: bob() };
+
+        let proposal_id = contract.queue_action(action.clone());
+        let queued = contract.get_queued_actions(None, None);
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].action, action);
+        assert_eq!(queued[0].eta, DEFAULT_GOVERNANCE_DELAY);
+
+        set_block_height(DEFAULT_GOVERNANCE_DELAY);
+        contract.execute_action(proposal_id);
+
+        assert_eq!(contract.get_contract_config().oracle_account_id, bob());
+        assert!(contract.get_queued_action(proposal_id).is_none());
+    }
+
+    #[test]
+    fn test_governance_delay_change() {
+        let mut contract = init();
+
+        let proposal_id = contract.queue_action(GovernanceAction::SetGovernanceDelay { delay: 10 });
+        set_block_height(DEFAULT_GOVERNANCE_DELAY);
+        contract.execute_action(proposal_id);
+        assert_eq!(contract.get_governance_delay(), 10);
+
+        let proposal_id = contract.queue_action(GovernanceAction::SetRewardToken { reward_token: bob() });
+        assert_eq!(contract.get_queued_action(proposal_id).unwrap().eta, DEFAULT_GOVERNANCE_DELAY + 10);
+    }
+
+    #[test]
+    fn test_add_market_action() {
+        let mut contract = init();
+        let market: AccountId = "dwnear.near".parse().unwrap();
+
+        let proposal_id = contract.queue_action(GovernanceAction::AddMarket { market: market.clone() });
+        assert!(contract.get_markets().is_empty());
+
+        set_block_height(DEFAULT_GOVERNANCE_DELAY);
+        contract.execute_action(proposal_id);
+        assert_eq!(contract.get_markets(), vec![market]);
+    }
+
+    #[test]
+    fn test_add_keeper_action() {
+        let mut contract = init();
+
+        let proposal_id = contract.queue_action(GovernanceAction::AddKeeper { keeper: bob() });
+        set_block_height(DEFAULT_GOVERNANCE_DELAY);
+        contract.execute_action(proposal_id);
+        assert_eq!(contract.get_keepers(), vec![bob()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Kink and reserve factor should be less or equal to 100%")]
+    fn failed_queue_invalid_interest_rate_model() {
+        let mut contract = init();
+
+        let model = InterestRateModel { kink: U128(RATIO_DECIMALS + 1), ..InterestRateModel::default() };
+        contract.queue_action(GovernanceAction::SetInterestRateModel { market: bob(), model });
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be executed before block")]
+    fn failed_execute_action_before_delay() {
+        let mut contract = init();
+
+        let proposal_id = contract.queue_action(GovernanceAction::SetRewardToken { reward_token: bob() });
+        set_block_height(DEFAULT_GOVERNANCE_DELAY - 1);
+        contract.execute_action(proposal_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal 0 doesn't exist")]
+    fn failed_execute_cancelled_action() {
+        let mut contract = init();
+
+        let proposal_id = contract.queue_action(GovernanceAction::SetRewardToken { reward_token: bob() });
+        contract.cancel_action(proposal_id);
+        assert!(contract.get_queued_actions(None, None).is_empty());
+
+        set_block_height(DEFAULT_GOVERNANCE_DELAY);
+        contract.execute_action(proposal_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "This functionality is allowed to be called by admin only")]
+    fn failed_queue_action_not_owner() {
+        let mut contract = init();
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(bob()).build());
+        contract.queue_action(GovernanceAction::SetRewardToken { reward_token: bob() });
+    }
+}
@@ -6,33 +6,50 @@ impl Contract {
         let mut result: Balance = 0;
         if let Some(map) = map_raw {
             for (asset, balance) in map.iter() {
-                let price = self.get_price(asset).unwrap();
-                result +=  Percentage::from(price.volatility).apply_to(price.value * balance);
+                let price = self.get_risk_price(asset).unwrap();
+                result += math::volatility_adjusted_value(balance, price.value, price.volatility);
             }
         }
         return  result;
     }
 
-    fn get_account_sum_per_action(&self, user_account: AccountId, action: ActionType) -> Balance {
+    /// Volatility-affected value of account supplies in a single market
+    pub fn get_collateral_value(&self, user_account: &AccountId, market: &AccountId) -> Balance {
+        let balance: Balance = self.get_entity_by_token(ActionType::Supply, user_account.clone(), market.clone());
+        if balance == 0 {
+            return 0;
+        }
+        let price = self.get_risk_price(market.clone()).unwrap();
+        return math::volatility_adjusted_value(balance, price.value, price.volatility);
+    }
+
+    /// Volatility-affected value of account supplies in markets enabled as collateral
+    pub fn get_collateral_sum(&self, user_account: &AccountId) -> Balance {
+        let mut result: Balance = 0;
+        if let Some(collaterals) = self.account_collaterals.get(user_account) {
+            for market in collaterals.iter() {
+                result += self.get_collateral_value(user_account, &market);
+            }
+        }
+        return result;
+    }
 
-        let map_raw: Option<UnorderedMap<AccountId, Balance>> = match action {
-            ActionType::Supply => self.account_supplies.get(&user_account),
-            ActionType::Borrow => self.account_borrows.get(&user_account),
-        };
+    fn get_account_sum_per_action(&self, user_account: AccountId, action: ActionType) -> Balance {
+        match action {
+            ActionType::Supply => self.get_collateral_sum(&user_account),
+            ActionType::Borrow => self.get_price_sum(self.account_borrows.get(&user_account)),
+        }
+    }
 
-        return self.get_price_sum(map_raw);
+    pub fn compute_health_factor(&self, collaterals: Balance, borrows: Balance) -> Ratio {
+        math::health_factor(collaterals, borrows)
     }
 
     pub fn get_health_factor(&self, user_account: AccountId ) -> Ratio {
-        let mut ratio = RATIO_DECIMALS;
         let collaterals = self.get_account_sum_per_action(user_account.clone(), ActionType::Supply);
         let borrows = self.get_account_sum_per_action(user_account.clone(), ActionType::Borrow);
 
-        if borrows != 0 {
-            ratio = collaterals * RATIO_DECIMALS / borrows;
-        }
-
-        return ratio;
+        return self.compute_health_factor(collaterals, borrows);
     }
 
 }
@@ -44,11 +61,13 @@ mod tests {
     // use crate::borrows_supplies::ActionType::{Borrow, Supply};
 
     use super::*;
+    use crate::test_utils::register_account;
 
     fn init() -> (Contract, AccountId, AccountId) {
         let (_owner_account, user_account) = (alice(), bob());
 
-        let mut controller_contract = Contract::new(Config { owner_id: user_account.clone(), oracle_account_id: user_account.clone() });
+        let mut controller_contract = Contract::new(Config::new(user_account.clone(), user_account.clone()));
+        register_account(&mut controller_contract, &user_account);
 
         let mut prices: Vec<Price> = Vec::new();
         prices.push(Price {
@@ -62,6 +81,9 @@ mod tests {
             volatility: 100
         });
 
+        for price in prices.iter() {
+            controller_contract.add_market(price.asset_id.clone());
+        }
         controller_contract.oracle_on_data(PriceJsonList {
             block_height: 83452949,
             price_list: prices
@@ -102,13 +124,13 @@ mod tests {
 
         assert_eq!(controller_contract.get_health_factor(user_account.clone()), RATIO_DECIMALS, "Test for account w/o collaterals and borrows has been failed");
 
-        controller_contract.increase_supplies(
+        controller_contract.internal_increase_supplies(
             user_account.clone(),
             AccountId::new_unchecked("wnear.near".to_string()),
             WBalance::from(balance)
         );
 
-        controller_contract.increase_borrows(
+        controller_contract.internal_increase_borrows(
             user_account.clone(),
             AccountId::new_unchecked("weth.near".to_string()),
             WBalance::from(balance)
@@ -1,20 +1,29 @@
-use near_sdk::{AccountId, Balance, BorshStorageKey, env, near_bindgen};
+use near_sdk::{AccountId, Balance, BorshStorageKey, env, ext_contract, near_bindgen};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 
-use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet};
 #[allow(unused_imports)]
 use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
-use percentage::Percentage;
 
 #[allow(unused_imports)]
 use general::*;
 
 pub use crate::borrows_supplies::*;
+pub use crate::collaterals::*;
 pub use crate::config::*;
 pub use crate::oraclehook::*;
+pub use crate::positions::*;
 pub use crate::prices::*;
 pub use crate::repay::*;
+pub use crate::rewards::*;
+pub use crate::governance::*;
+pub use crate::bad_debt::*;
+pub use crate::liquidation::*;
+pub use crate::twap::*;
+pub use crate::borrowers::*;
+pub use crate::storage::*;
+pub use crate::upgrade::*;
 
 
 #[allow(unused_imports)]
@@ -23,8 +32,19 @@ mod oraclehook;
 mod prices;
 pub mod borrows_supplies;
 pub mod repay;
+#[cfg(test)]
 mod test_utils;
 mod healthfactor;
+mod positions;
+mod collaterals;
+mod storage;
+mod upgrade;
+mod rewards;
+mod governance;
+mod bad_debt;
+mod liquidation;
+mod twap;
+mod borrowers;
 
 
 
@@ -32,11 +52,30 @@ mod healthfactor;
 pub enum StorageKeys {
     Markets,
     Supplies,
+    /// Legacy prefix shared by all accounts, kept for migration only
     SuppliesToken,
+    /// Legacy prefix shared by all accounts, kept for migration only
     BorrowsToken,
     Prices,
     Config,
     Borrows,
+    AccountSupplies { account_hash: Vec<u8> },
+    AccountBorrows { account_hash: Vec<u8> },
+    Collaterals,
+    AccountCollaterals { account_hash: Vec<u8> },
+    AccountStorages,
+    StateVersion,
+    MarketRewards,
+    AccountRewardIndexes,
+    AccountRewardIndexesEntry { account_hash: Vec<u8> },
+    AccountAccruedRewards,
+    GovernanceProposals,
+    Keepers,
+    PriceAccumulators,
+    Borrowers,
+    PriceUpdates,
+    ListedMarkets,
+    PriceAssets,
 }
 
 #[near_bindgen]
@@ -56,6 +95,57 @@ pub struct Contract {
 
     /// Contract configuration object
     pub config: LazyOption<Config>,
+
+    /// User Account ID -> Dtoken addresses used as collateral
+    pub account_collaterals: LookupMap<AccountId, UnorderedSet<AccountId>>,
+
+    /// User Account ID -> Storage deposit and usage
+    pub account_storages: LookupMap<AccountId, AccountStorage>,
+
+    /// Dtoken address -> Reward emission and indexes of the market
+    pub market_rewards: LookupMap<AccountId, MarketRewards>,
+
+    /// User Account ID -> Dtoken address -> Reward index snapshots
+    pub account_reward_indexes: LookupMap<AccountId, UnorderedMap<AccountId, RewardIndexes>>,
+
+    /// User Account ID -> Accrued and not claimed rewards
+    pub account_accrued_rewards: LookupMap<AccountId, Balance>,
+
+    /// NEP-141 token paid as liquidity mining reward
+    pub reward_token: Option<AccountId>,
+
+    /// Number of blocks between queueing and execution of governance actions
+    pub governance_delay: near_sdk::BlockHeight,
+
+    /// Proposal ID -> Queued governance action
+    pub governance_proposals: UnorderedMap<u64, GovernanceProposal>,
+
+    /// ID assigned to the next queued proposal
+    pub next_proposal_id: u64,
+
+    /// Accounts allowed to perform maintenance actions along with the owner
+    pub keepers: UnorderedSet<AccountId>,
+
+    /// Asset ID -> Cumulative price and its observations
+    pub price_accumulators: LookupMap<AccountId, PriceAccumulator>,
+
+    /// Price used for health factor and liquidations
+    pub price_mode: PriceMode,
+
+    /// Number of blocks TWAP is computed over
+    pub twap_window: near_sdk::BlockHeight,
+
+    /// Accounts with open borrows
+    pub borrowers: UnorderedSet<AccountId>,
+
+    /// Asset ID -> Last price update metadata
+    pub price_updates: UnorderedMap<AccountId, PriceUpdate>,
+
+    /// Dtoken addresses of markets the oracle can push prices for
+    pub listed_markets: UnorderedSet<AccountId>,
+
+    /// Asset IDs of stored prices, prices stored by the released contract are added with index_prices
+    pub price_assets: UnorderedSet<AccountId>,
 }
 
 impl Default for Contract {
@@ -64,18 +154,35 @@ impl Default for Contract {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
-pub struct PriceJsonList {
-    /// Block number
-    pub block_height: u64,
+#[ext_contract(ext_dtoken)]
+trait DtokenInterface {
+    fn write_off_bad_debt(&mut self, account: AccountId) -> WBalance;
+    fn seize(&mut self, borrower: AccountId, liquidator: AccountId, seized_amount: WBalance, protocol_fee: WBalance);
+    fn set_interest_rate_model(&mut self, model: InterestRateModel);
+    fn set_flash_loan_fee(&mut self, fee: WRatio);
+    fn set_native_market(&mut self, enabled: bool);
+    fn set_same_block_guard(&mut self, enabled: bool);
+    fn add_flash_loan_receiver(&mut self, receiver: AccountId);
+    fn remove_flash_loan_receiver(&mut self, receiver: AccountId);
+}
 
-    /// Vector of asset prices
-    pub price_list: Vec<Price>,
+#[ext_contract(ext_self)]
+trait InternalControllerInterface {
+    fn claim_rewards_callback(&mut self, account: AccountId, amount: WBalance);
+    fn write_off_bad_debt_callback(&mut self, account: AccountId, market: AccountId);
+    fn liquidation_callback(
+        &mut self,
+        borrower: AccountId,
+        borrowing_market: AccountId,
+        collateral_market: AccountId,
+        liquidator: AccountId,
+        recorded_repaid_amount: WBalance,
+        result: LiquidationResult,
+    ) -> LiquidationResult;
 }
 
 pub trait OraclePriceHandlerHook {
-    fn oracle_on_data(&mut self, price_data: PriceJsonList);
+    fn oracle_on_data(&mut self, price_data: PriceJsonList) -> OracleDataResult;
 }
 
 #[near_bindgen]
@@ -83,12 +190,38 @@ impl Contract {
     /// Initializes the contract with the given config. Needs to be called once.
     #[init]
     pub fn new(config: Config) -> Self {
+        Self::new_with_governance_delay(config, DEFAULT_GOVERNANCE_DELAY)
+    }
+
+    /// Initializes the contract with the given config and governance delay. Needs to be called once.
+    #[init]
+    pub fn new_with_governance_delay(config: Config, governance_delay: near_sdk::BlockHeight) -> Self {
+        config.assert_valid();
+        events::emit::config_change("config", &config);
+        Self::write_state_version(STATE_VERSION);
         Self {
             markets: LookupMap::new(StorageKeys::Markets),
             account_supplies: LookupMap::new(StorageKeys::Supplies),
             account_borrows: LookupMap::new(StorageKeys::Borrows),
             prices: LookupMap::new(StorageKeys::Prices),
             config: LazyOption::new(StorageKeys::Config, Some(&config)),
+            account_collaterals: LookupMap::new(StorageKeys::Collaterals),
+            account_storages: LookupMap::new(StorageKeys::AccountStorages),
+            market_rewards: LookupMap::new(StorageKeys::MarketRewards),
+            account_reward_indexes: LookupMap::new(StorageKeys::AccountRewardIndexes),
+            account_accrued_rewards: LookupMap::new(StorageKeys::AccountAccruedRewards),
+            reward_token: None,
+            governance_delay,
+            governance_proposals: UnorderedMap::new(StorageKeys::GovernanceProposals),
+            next_proposal_id: 0,
+            keepers: UnorderedSet::new(StorageKeys::Keepers),
+            price_accumulators: LookupMap::new(StorageKeys::PriceAccumulators),
+            price_mode: PriceMode::Spot,
+            twap_window: DEFAULT_TWAP_WINDOW,
+            borrowers: UnorderedSet::new(StorageKeys::Borrowers),
+            price_updates: UnorderedMap::new(StorageKeys::PriceUpdates),
+            listed_markets: UnorderedSet::new(StorageKeys::ListedMarkets),
+            price_assets: UnorderedSet::new(StorageKeys::PriceAssets),
         }
     }
 }
\ No newline at end of file
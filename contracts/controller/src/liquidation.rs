@@ -0,0 +1,237 @@
+use crate::*;
+
+use near_sdk::{is_promise_success, log, Promise};
+
+impl Contract {
+    /// Discount of seized collateral, (1 - health factor) / 2 limited by configured floor and cap
+    pub fn get_liquidation_discount(&self, health_factor: Ratio) -> Ratio {
+        let config = self.get_contract_config();
+        math::liquidation_discount(
+            health_factor,
+            config.liquidation_discount_floor.into(),
+            config.liquidation_discount_cap.into(),
+        )
+    }
+
+    /// Amount of collateral tokens worth the repaid amount of borrowed tokens with the discount applied
+    pub fn get_seize_amount(&self, borrowing_market: &AccountId, collateral_market: &AccountId, repaid_amount: Balance, discount: Ratio) -> Balance {
+        let borrow_price = self
+            .get_risk_price(borrowing_market.clone())
+            .unwrap_or_else(|| env::panic_str(&format!("Price of {} isn't set", borrowing_market)));
+        let collateral_price = self
+            .get_risk_price(collateral_market.clone())
+            .unwrap_or_else(|| env::panic_str(&format!("Price of {} isn't set", collateral_market)));
+
+        math::seize_amount(repaid_amount, borrow_price.value, collateral_price.value, discount)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Repays a part of the borrow of an unhealthy account and moves its collateral to the liquidator.
+    /// Called by the borrowing market, repaid amount is limited by max close factor of the borrow_amount,
+    /// part of seized collateral goes to reserves of the collateral market as protocol fee.
+    pub fn liquidation(
+        &mut self,
+        borrower: AccountId,
+        borrowing_market: AccountId,
+        collateral_market: AccountId,
+        liquidator: AccountId,
+        liquidation_amount: WBalance,
+        borrow_amount: WBalance,
+    ) -> Promise {
+        self.assert_market_caller(&borrowing_market);
+        assert_ne!(borrower, liquidator, "Account can't liquidate itself");
+        self.sync_borrow_balance(&borrower, &borrowing_market, borrow_amount);
+        assert!(
+            self.is_collateral(&borrower, &collateral_market),
+            "Market {} isn't used as collateral by {}",
+            collateral_market,
+            borrower
+        );
+
+        let health_factor = self.get_health_factor(borrower.clone());
+        assert!(
+            health_factor < RATIO_DECIMALS,
+            "Account {} can't be liquidated, health factor is {}",
+            borrower,
+            health_factor
+        );
+
+        let config = self.get_contract_config();
+        let max_repay_amount = math::max_repay_amount(borrow_amount.into(), config.max_close_factor.into());
+        let repaid_amount = std::cmp::min(Balance::from(liquidation_amount), max_repay_amount);
+        assert!(repaid_amount > 0, "Nothing can be repaid for account {}", borrower);
+
+        let discount = self.get_liquidation_discount(health_factor);
+        let seized_amount = self.get_seize_amount(&borrowing_market, &collateral_market, repaid_amount, discount);
+        let protocol_fee = math::liquidation_fee(seized_amount, config.liquidation_fee.into());
+        let collateral = self.get_entity_by_token(ActionType::Supply, borrower.clone(), collateral_market.clone());
+        assert!(
+            seized_amount <= collateral,
+            "Not enough collateral of {} in market {} to seize {} tokens",
+            borrower,
+            collateral_market,
+            seized_amount
+        );
+
+        self.internal_decrease_borrows(borrower.clone(), borrowing_market.clone(), U128(repaid_amount));
+        self.internal_decrease_supplies(borrower.clone(), collateral_market.clone(), U128(seized_amount));
+        self.internal_increase_supplies(liquidator.clone(), collateral_market.clone(), U128(seized_amount - protocol_fee));
+
+        let result = LiquidationResult {
+            repaid_amount: U128(repaid_amount),
+            seized_amount: U128(seized_amount),
+            protocol_fee: U128(protocol_fee),
+        };
+
+        ext_dtoken::seize(
+            borrower.clone(),
+            liquidator.clone(),
+            U128(seized_amount),
+            U128(protocol_fee),
+            collateral_market.clone(),
+            NO_DEPOSIT,
+            TGAS * 20,
+        )
+        .then(ext_self::liquidation_callback(
+            borrower,
+            borrowing_market,
+            collateral_market,
+            liquidator,
+            U128(repaid_amount),
+            result,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            TGAS * 20,
+        ))
+    }
+
+    /// Restores positions if collateral wasn't seized, zero result tells the market to refund the liquidator
+    #[private]
+    pub fn liquidation_callback(
+        &mut self,
+        borrower: AccountId,
+        borrowing_market: AccountId,
+        collateral_market: AccountId,
+        liquidator: AccountId,
+        recorded_repaid_amount: WBalance,
+        result: LiquidationResult,
+    ) -> LiquidationResult {
+        let seized_amount = Balance::from(result.seized_amount);
+        let protocol_fee = Balance::from(result.protocol_fee);
+
+        if !is_promise_success() {
+            log!("Failed to seize collateral of {} in market {}, liquidation is reverted", borrower, collateral_market);
+            self.internal_decrease_supplies(liquidator.clone(), collateral_market.clone(), U128(seized_amount - protocol_fee));
+            self.internal_increase_supplies(borrower.clone(), collateral_market, U128(seized_amount));
+            self.internal_increase_borrows(borrower, borrowing_market, recorded_repaid_amount);
+            return LiquidationResult::default();
+        }
+
+        events::emit::liquidate(
+            &liquidator,
+            &borrower,
+            &borrowing_market,
+            &collateral_market,
+            result.repaid_amount.into(),
+            seized_amount,
+        );
+        result
+    }
+
+    pub fn get_liquidation_discount_for(&self, account: AccountId) -> WRatio {
+        U128(self.get_liquidation_discount(self.get_health_factor(account)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::register_account;
+
+    fn init() -> (Contract, AccountId, AccountId) {
+        let mut contract = Contract::new(Config::new(alice(), alice()));
+        let borrowing_market: AccountId = "dusdt.near".parse().unwrap();
+        let collateral_market: AccountId = "dwnear.near".parse().unwrap();
+
+        contract.upsert_price(&Price { asset_id: borrowing_market.clone(), value: 1, volatility: 100 });
+        contract.upsert_price(&Price { asset_id: collateral_market.clone(), value: 1, volatility: 100 });
+
+        register_account(&mut contract, &bob());
+        register_account(&mut contract, &carol());
+        contract.internal_increase_supplies(carol(), collateral_market.clone(), U128(1000));
+        contract.internal_increase_borrows(carol(), borrowing_market.clone(), U128(1250));
+
+        contract.add_market(borrowing_market.clone());
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(borrowing_market.clone()).build());
+        (contract, borrowing_market, collateral_market)
+    }
+
+    #[test]
+    fn test_liquidation_discount_limits() {
+        let (mut contract, _, _) = init();
+
+        // (100% - 80%) / 2
+        assert_eq!(contract.get_liquidation_discount(8000), 1000);
+        assert_eq!(contract.get_liquidation_discount(9900), 200);
+        assert_eq!(contract.get_liquidation_discount(1000), 2000);
+
+        contract.set_liquidation_config(U128(5000), U128(0), U128(500), U128(0));
+        assert_eq!(contract.get_liquidation_discount(8000), 500);
+    }
+
+    #[test]
+    fn test_liquidation_respects_close_factor() {
+        let (mut contract, borrowing_market, collateral_market) = init();
+
+        contract.liquidation(carol(), borrowing_market.clone(), collateral_market.clone(), bob(), U128(1000), U128(1250));
+
+        // Health factor is 80%, so discount is 10% and 625 repaid tokens bring 694 collateral tokens, 3% of them is fee
+        assert_eq!(contract.get_entity_by_token(ActionType::Borrow, carol(), borrowing_market), 625);
+        assert_eq!(contract.get_entity_by_token(ActionType::Supply, carol(), collateral_market.clone()), 1000 - 694);
+        assert_eq!(contract.get_entity_by_token(ActionType::Supply, bob(), collateral_market), 694 - 20);
+    }
+
+    #[test]
+    fn test_liquidation_syncs_accrued_borrow() {
+        let (mut contract, borrowing_market, collateral_market) = init();
+
+        // Market accrued 50 tokens of interest since the last borrow change
+        contract.liquidation(carol(), borrowing_market.clone(), collateral_market, bob(), U128(1000), U128(1300));
+
+        assert_eq!(contract.get_entity_by_token(ActionType::Borrow, carol(), borrowing_market), 650);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be liquidated, health factor is")]
+    fn failed_liquidation_of_healthy_account() {
+        let (mut contract, borrowing_market, collateral_market) = init();
+        contract.internal_increase_supplies(carol(), collateral_market.clone(), U128(1000));
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(borrowing_market.clone()).build());
+        contract.liquidation(carol(), borrowing_market, collateral_market, bob(), U128(100), U128(1250));
+    }
+
+    #[test]
+    #[should_panic(expected = "allowed to be called by listed market dusdt.near only")]
+    fn failed_liquidation_not_market() {
+        let (mut contract, borrowing_market, collateral_market) = init();
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(bob()).build());
+        contract.liquidation(carol(), borrowing_market, collateral_market, bob(), U128(100), U128(1250));
+    }
+
+    #[test]
+    #[should_panic(expected = "Liquidation discount floor should be less or equal to the cap")]
+    fn failed_set_liquidation_config_floor_above_cap() {
+        let (mut contract, _, _) = init();
+
+        contract.set_liquidation_config(U128(5000), U128(1000), U128(500), U128(0));
+    }
+}
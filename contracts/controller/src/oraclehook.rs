@@ -1,8 +1,74 @@
 use crate::*;
 
+use std::collections::HashMap;
+
+use near_sdk::BlockHeight;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum PriceRejectReason {
+    /// Price value is 0
+    ZeroPrice,
+    /// Volatility is above 100%
+    InvalidVolatility,
+    /// Asset is present in the payload more than once
+    DuplicateAsset,
+    /// Asset isn't a listed market
+    UnlistedMarket,
+    /// Block height isn't newer than the one of the stored price
+    StaleBlockHeight,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RejectedPrice {
+    /// Asset Id
+    pub asset_id: AccountId,
+
+    /// Reason the price wasn't applied
+    pub reason: PriceRejectReason,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OracleDataResult {
+    /// Assets with applied prices
+    pub accepted: Vec<AccountId>,
+
+    /// Prices which weren't applied
+    pub rejected: Vec<RejectedPrice>,
+}
+
+impl Contract {
+    fn validate_price(&self, price: &Price, block_height: BlockHeight, occurrences: usize) -> Result<(), PriceRejectReason> {
+        if price.value == 0 {
+            return Err(PriceRejectReason::ZeroPrice);
+        }
+        if price.volatility > 100 {
+            return Err(PriceRejectReason::InvalidVolatility);
+        }
+        // All entries of a duplicated asset are rejected, as it's unknown which one is right
+        if occurrences > 1 {
+            return Err(PriceRejectReason::DuplicateAsset);
+        }
+        if !self.is_listed_market(&price.asset_id) {
+            return Err(PriceRejectReason::UnlistedMarket);
+        }
+
+        // A price of the same block height is a replay of the stored one
+        let last_update = self.price_updates.get(&price.asset_id);
+        if last_update.map_or(false, |update| block_height <= update.oracle_block_height) {
+            return Err(PriceRejectReason::StaleBlockHeight);
+        }
+        Ok(())
+    }
+}
+
 #[near_bindgen]
 impl OraclePriceHandlerHook for Contract {
-    fn oracle_on_data(&mut self, price_data: PriceJsonList) {
+    /// Applies valid prices, invalid ones are skipped and reported in the result and price_rejected events
+    fn oracle_on_data(&mut self, price_data: PriceJsonList) -> OracleDataResult {
         let config: Config = self.get_contract_config();
 
         assert_eq!(
@@ -13,10 +79,118 @@ impl OraclePriceHandlerHook for Contract {
             env::predecessor_account_id().to_string()
         );
 
+        let mut occurrences: HashMap<AccountId, usize> = HashMap::new();
+        for price in price_data.price_list.iter() {
+            *occurrences.entry(price.asset_id.clone()).or_insert(0) += 1;
+        }
+
+        let mut result = OracleDataResult::default();
         for price in price_data.price_list {
-            self.upsert_price(&price);
+            match self.validate_price(&price, price_data.block_height, occurrences[&price.asset_id]) {
+                Ok(()) => {
+                    // Markets priced by the released contract are listed on their first update
+                    if self.listed_markets.insert(&price.asset_id) {
+                        events::emit::config_change("add_market", &price.asset_id);
+                    }
+                    self.upsert_oracle_price(&price, price_data.block_height);
+                    events::emit::price_update(&price.asset_id, price.value, price.volatility, price_data.block_height);
+                    result.accepted.push(price.asset_id);
+                }
+                Err(reason) => {
+                    events::emit::price_rejected(&price, price_data.block_height, &reason);
+                    result.rejected.push(RejectedPrice { asset_id: price.asset_id, reason });
+                }
+            }
         }
+        result
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::alice;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
 
+    fn init() -> (Contract, AccountId) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+        let mut contract = Contract::new(Config::new(alice(), alice()));
+        let market: AccountId = "dwnear.near".parse().unwrap();
+        contract.add_market(market.clone());
+        (contract, market)
+    }
+
+    fn push(contract: &mut Contract, block_height: BlockHeight, price_list: Vec<Price>) -> OracleDataResult {
+        contract.oracle_on_data(PriceJsonList { block_height, price_list })
+    }
+
+    fn reasons(result: &OracleDataResult) -> Vec<PriceRejectReason> {
+        result.rejected.iter().map(|rejected| rejected.reason).collect()
+    }
+
+    #[test]
+    fn test_invalid_prices_are_rejected() {
+        let (mut contract, market) = init();
+        let unlisted: AccountId = "dusdt.near".parse().unwrap();
+
+        let result = push(&mut contract, 10, vec![
+            Price { asset_id: market.clone(), value: 0, volatility: 100 },
+            Price { asset_id: market.clone(), value: 10, volatility: 101 },
+            Price { asset_id: unlisted, value: 1, volatility: 100 },
+        ]);
+        assert!(result.accepted.is_empty());
+        assert_eq!(reasons(&result), vec![
+            PriceRejectReason::ZeroPrice,
+            PriceRejectReason::InvalidVolatility,
+            PriceRejectReason::UnlistedMarket,
+        ]);
+        assert!(contract.get_price(market).is_none());
+    }
+
+    #[test]
+    fn test_duplicate_assets_are_rejected() {
+        let (mut contract, market) = init();
+
+        let result = push(&mut contract, 10, vec![
+            Price { asset_id: market.clone(), value: 10, volatility: 100 },
+            Price { asset_id: market.clone(), value: 20, volatility: 100 },
+        ]);
+        assert_eq!(reasons(&result), vec![PriceRejectReason::DuplicateAsset, PriceRejectReason::DuplicateAsset]);
+        assert!(contract.get_price(market).is_none());
+    }
+
+    #[test]
+    fn test_block_heights_are_monotonic() {
+        let (mut contract, market) = init();
+
+        let result = push(&mut contract, 10, vec![Price { asset_id: market.clone(), value: 10, volatility: 100 }]);
+        assert_eq!(result.accepted, vec![market.clone()]);
+
+        let result = push(&mut contract, 9, vec![Price { asset_id: market.clone(), value: 20, volatility: 100 }]);
+        assert_eq!(reasons(&result), vec![PriceRejectReason::StaleBlockHeight]);
+        assert_eq!(contract.get_price(market.clone()).unwrap().value, U128(10));
+
+        let result = push(&mut contract, 10, vec![Price { asset_id: market.clone(), value: 30, volatility: 100 }]);
+        assert_eq!(reasons(&result), vec![PriceRejectReason::StaleBlockHeight]);
+        assert_eq!(contract.get_price(market.clone()).unwrap().value, U128(10));
+
+        push(&mut contract, 11, vec![Price { asset_id: market.clone(), value: 30, volatility: 100 }]);
+        assert_eq!(contract.get_price(market).unwrap().value, U128(30));
+    }
+
+    #[test]
+    fn test_priced_market_of_released_contract_is_listed() {
+        let (mut contract, _) = init();
+        let legacy_market: AccountId = "dusdt.near".parse().unwrap();
+
+        // Released contract stored prices without listing markets
+        contract.prices.insert(&legacy_market, &Price { asset_id: legacy_market.clone(), value: 1, volatility: 100 });
+        assert!(contract.is_listed_market(&legacy_market));
+
+        let result = push(&mut contract, 10, vec![Price { asset_id: legacy_market.clone(), value: 2, volatility: 100 }]);
+        assert_eq!(result.accepted, vec![legacy_market.clone()]);
+        assert!(contract.get_markets().contains(&legacy_market));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::*;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MarketPosition {
+    /// Dtoken address of the market
+    pub market: AccountId,
+
+    /// Supplied amount of underlying tokens
+    pub supplied: WBalance,
+
+    /// Borrowed amount of underlying tokens
+    pub borrowed: WBalance,
+
+    /// Supplied amount estimated in USD
+    pub supplied_usd: WBalance,
+
+    /// Borrowed amount estimated in USD
+    pub borrowed_usd: WBalance,
+
+    /// Whether supplies of the market are used as collateral
+    pub is_collateral: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountPosition {
+    /// User Account ID
+    pub account_id: AccountId,
+
+    /// Every market the account has supplies or borrows in
+    pub markets: Vec<MarketPosition>,
+
+    /// Sum of all supplies estimated in USD
+    pub total_supplied_usd: WBalance,
+
+    /// Sum of all borrows estimated in USD
+    pub total_borrowed_usd: WBalance,
+
+    /// Volatility-affected sum of supplies, the value used as collateral
+    pub collateral_value: WBalance,
+
+    /// Collateral value which isn't covering borrows yet
+    pub borrow_power: WBalance,
+
+    /// Account health factor, RATIO_DECIMALS is 100%
+    pub health_factor: WRatio,
+
+    /// Whether the health factor is below 100%
+    pub is_liquidatable: bool,
+}
+
+impl Contract {
+    fn get_usd_value(&self, asset: &AccountId, balance: Balance) -> Balance {
+        self.get_spot_price(asset)
+            .map(|price| price.value * balance)
+            .unwrap_or(0)
+    }
+
+    fn get_risk_value(&self, asset: &AccountId, balance: Balance) -> Balance {
+        self.get_risk_price(asset.clone())
+            .map(|price| math::volatility_adjusted_value(balance, price.value, price.volatility))
+            .unwrap_or(0)
+    }
+
+    fn get_market_position<'a>(positions: &'a mut Vec<MarketPosition>, market: &AccountId) -> &'a mut MarketPosition {
+        let index = match positions.iter().position(|position| &position.market == market) {
+            Some(index) => index,
+            None => {
+                positions.push(MarketPosition {
+                    market: market.clone(),
+                    supplied: U128(0),
+                    borrowed: U128(0),
+                    supplied_usd: U128(0),
+                    borrowed_usd: U128(0),
+                    is_collateral: false,
+                });
+                positions.len() - 1
+            }
+        };
+        &mut positions[index]
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Markets without a price are valued at 0, so the position of any account can be viewed
+    pub fn get_account_position(&self, account: AccountId) -> AccountPosition {
+        let mut markets: Vec<MarketPosition> = Vec::new();
+        let mut total_supplied_usd: Balance = 0;
+        let mut total_borrowed_usd: Balance = 0;
+        let mut collateral_value: Balance = 0;
+        let mut borrows_value: Balance = 0;
+
+        if let Some(supplies) = self.account_supplies.get(&account) {
+            for (market, balance) in supplies.iter() {
+                if balance == 0 {
+                    continue;
+                }
+                let usd_value = self.get_usd_value(&market, balance);
+                let position = Contract::get_market_position(&mut markets, &market);
+                position.supplied = U128(balance);
+                position.supplied_usd = U128(usd_value);
+                position.is_collateral = self.is_collateral(&account, &market);
+                if position.is_collateral {
+                    collateral_value += self.get_risk_value(&market, balance);
+                }
+                total_supplied_usd += usd_value;
+            }
+        }
+
+        if let Some(borrows) = self.account_borrows.get(&account) {
+            for (market, balance) in borrows.iter() {
+                if balance == 0 {
+                    continue;
+                }
+                let usd_value = self.get_usd_value(&market, balance);
+                let position = Contract::get_market_position(&mut markets, &market);
+                position.borrowed = U128(balance);
+                position.borrowed_usd = U128(usd_value);
+                borrows_value += self.get_risk_value(&market, balance);
+                total_borrowed_usd += usd_value;
+            }
+        }
+
+        let health_factor = self.compute_health_factor(collateral_value, borrows_value);
+
+        AccountPosition {
+            account_id: account,
+            markets,
+            total_supplied_usd: U128(total_supplied_usd),
+            total_borrowed_usd: U128(total_borrowed_usd),
+            collateral_value: U128(collateral_value),
+            borrow_power: U128(collateral_value.saturating_sub(borrows_value)),
+            health_factor: U128(health_factor),
+            is_liquidatable: health_factor < RATIO_DECIMALS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob};
+
+    use super::*;
+    use crate::test_utils::register_account;
+
+    fn init() -> (Contract, AccountId) {
+        let (_owner_account, user_account) = (alice(), bob());
+
+        let mut controller_contract = Contract::new(Config::new(user_account.clone(), user_account.clone()));
+        register_account(&mut controller_contract, &user_account);
+
+        let mut prices: Vec<Price> = Vec::new();
+        prices.push(Price {
+            asset_id: AccountId::new_unchecked("wnear.near".to_string()),
+            value: 2,
+            volatility: 80
+        });
+        prices.push(Price {
+            asset_id: AccountId::new_unchecked("weth.near".to_string()),
+            value: 3,
+            volatility: 100
+        });
+
+        for price in prices.iter() {
+            controller_contract.add_market(price.asset_id.clone());
+        }
+        controller_contract.oracle_on_data(PriceJsonList {
+            block_height: 83452949,
+            price_list: prices
+        });
+
+        return (controller_contract, user_account);
+    }
+
+    #[test]
+    fn test_for_get_account_position() {
+        let (mut controller_contract, user_account) = init();
+
+        let position = controller_contract.get_account_position(user_account.clone());
+        assert_eq!(position.markets.len(), 0, "Account w/o supplies and borrows shouldn't have markets");
+        assert_eq!(position.health_factor, U128(RATIO_DECIMALS));
+        assert_eq!(position.is_liquidatable, false);
+
+        controller_contract.internal_increase_supplies(
+            user_account.clone(),
+            AccountId::new_unchecked("wnear.near".to_string()),
+            WBalance::from(100)
+        );
+        controller_contract.internal_increase_borrows(
+            user_account.clone(),
+            AccountId::new_unchecked("weth.near".to_string()),
+            WBalance::from(50)
+        );
+
+        let position = controller_contract.get_account_position(user_account.clone());
+        assert_eq!(position.markets.len(), 2, "Account should have supply and borrow markets");
+        assert_eq!(position.markets[0].supplied, U128(100));
+        assert_eq!(position.markets[0].supplied_usd, U128(200));
+        assert_eq!(position.markets[0].is_collateral, true);
+        assert_eq!(position.markets[1].borrowed, U128(50));
+        assert_eq!(position.markets[1].borrowed_usd, U128(150));
+        assert_eq!(position.total_supplied_usd, U128(200));
+        assert_eq!(position.total_borrowed_usd, U128(150));
+        assert_eq!(position.collateral_value, U128(160));
+        assert_eq!(position.borrow_power, U128(10));
+        assert_eq!(position.health_factor, U128(160 * RATIO_DECIMALS / 150));
+        assert_eq!(position.is_liquidatable, false);
+    }
+
+    #[test]
+    fn test_get_account_position_with_unpriced_market() {
+        let (mut controller_contract, user_account) = init();
+        let unpriced_market = AccountId::new_unchecked("wbtc.near".to_string());
+        controller_contract.add_market(unpriced_market.clone());
+
+        controller_contract.internal_increase_supplies(
+            user_account.clone(),
+            AccountId::new_unchecked("wnear.near".to_string()),
+            WBalance::from(100)
+        );
+        controller_contract.internal_increase_supplies(user_account.clone(), unpriced_market.clone(), WBalance::from(10));
+        controller_contract.internal_increase_borrows(user_account.clone(), unpriced_market, WBalance::from(5));
+
+        let position = controller_contract.get_account_position(user_account);
+        assert_eq!(position.markets.len(), 2);
+        assert_eq!(position.markets[1].supplied, U128(10));
+        assert_eq!(position.markets[1].supplied_usd, U128(0));
+        assert_eq!(position.markets[1].borrowed, U128(5));
+        assert_eq!(position.total_supplied_usd, U128(200));
+        assert_eq!(position.collateral_value, U128(160));
+        assert_eq!(position.borrow_power, U128(160));
+    }
+}
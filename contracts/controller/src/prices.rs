@@ -1,24 +1,106 @@
 use crate::*;
 
+use near_sdk::json_types::U64;
+use near_sdk::BlockHeight;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceUpdate {
+    /// BlockHeight of the update
+    pub block_height: BlockHeight,
+
+    /// Block timestamp of the update in nanoseconds
+    pub timestamp: U64,
+
+    /// BlockHeight reported by the oracle along with the price
+    pub oracle_block_height: BlockHeight,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceInfo {
+    /// Asset Id
+    pub asset_id: AccountId,
+
+    /// Asset price value
+    pub value: WBalance,
+
+    /// Asset volatility value, 0..100%
+    pub volatility: WPercent,
+
+    /// Metadata of the last update, absent for prices not updated since metadata is recorded
+    pub last_update: Option<PriceUpdate>,
+}
+
 impl Contract {
-    pub fn get_prices_for_assets(&self, assets: Vec<AccountId>) -> LookupMap<AccountId, Balance> {
-        let mut result = LookupMap::new(b"t".to_vec());
-        for asset in assets {
-            if self.prices.contains_key(&asset) {
-                let price = self.get_price(asset).unwrap();
-                result.insert(&price.asset_id, &price.value);
-            }
-        }
-        return result;
+    /// Last price pushed by the oracle
+    pub fn get_spot_price(&self, asset_id: &AccountId) -> Option<Price> {
+        self.prices.get(asset_id)
     }
 
-    pub fn get_price(&self, asset_id: AccountId) -> Option<Price> {
-        return self.prices.get(&asset_id);
+    pub fn upsert_price(&mut self, price: &Price) {
+        self.upsert_oracle_price(price, env::block_height());
     }
 
-    pub fn upsert_price(&mut self, price: &Price) {
+    pub fn upsert_oracle_price(&mut self, price: &Price, oracle_block_height: BlockHeight) {
         // Update & insert operation
-        self.prices.insert(&price.asset_id, &price);
+        self.prices.insert(&price.asset_id, price);
+        self.price_assets.insert(&price.asset_id);
+        self.price_updates.insert(
+            &price.asset_id,
+            &PriceUpdate {
+                block_height: env::block_height(),
+                timestamp: U64(env::block_timestamp()),
+                oracle_block_height,
+            },
+        );
+        self.record_price(price);
+    }
+
+    fn get_price_info(&self, asset_id: &AccountId) -> Option<PriceInfo> {
+        self.get_spot_price(asset_id).map(|price| PriceInfo {
+            asset_id: price.asset_id,
+            value: U128(price.value),
+            volatility: U128(price.volatility),
+            last_update: self.price_updates.get(asset_id),
+        })
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_price(&self, asset_id: AccountId) -> Option<PriceInfo> {
+        self.get_price_info(&asset_id)
+    }
+
+    /// Prices of the given assets, assets without a price are skipped
+    pub fn get_prices(&self, asset_ids: Vec<AccountId>) -> Vec<PriceInfo> {
+        asset_ids
+            .iter()
+            .filter_map(|asset_id| self.get_price_info(asset_id))
+            .collect()
+    }
+
+    /// Stored prices in order of their first update, prices stored by the released contract
+    /// are listed once indexed and have no last update until the oracle pushes them again
+    pub fn list_prices(&self, from_index: u64, limit: u64) -> Vec<PriceInfo> {
+        self.price_assets
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|asset_id| self.get_price_info(&asset_id))
+            .collect()
+    }
+
+    /// Adds assets which got prices before the price index was introduced, assets without a price are skipped
+    pub fn index_prices(&mut self, asset_ids: Vec<AccountId>) {
+        self.assert_owner();
+
+        for asset_id in asset_ids.iter() {
+            if self.prices.contains_key(asset_id) {
+                self.price_assets.insert(asset_id);
+            }
+        }
     }
 }
 
@@ -27,6 +109,8 @@ mod tests {
 
     use near_sdk::AccountId;
     use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
     use assert_matches::assert_matches;
 
     use crate::{Config, Contract};
@@ -34,7 +118,7 @@ mod tests {
     pub fn init_test_env() -> (Contract, AccountId, AccountId) {
         let (owner_account, oracle_account, user_account) = (alice(), bob(), carol());
 
-        let eth_contract = Contract::new(Config { owner_id: owner_account, oracle_account_id: oracle_account });
+        let eth_contract = Contract::new(Config::new(owner_account, oracle_account));
 
         let token_address: AccountId = "near".parse().unwrap();
 
@@ -58,8 +142,46 @@ mod tests {
 
         let gotten_price = near_contract.get_price(token_address).unwrap();
         assert_matches!(&gotten_price, _price, "Get price format check has been failed");
-        assert_eq!(&gotten_price.value, &price.value, "Get price values check has been failed");
-        assert_eq!(&gotten_price.volatility, &price.volatility,  "Get price volatility check has been failed");
+        assert_eq!(gotten_price.value, U128(price.value), "Get price values check has been failed");
+        assert_eq!(gotten_price.volatility, U128(price.volatility),  "Get price volatility check has been failed");
         assert_eq!(&gotten_price.asset_id, &price.asset_id, "Get price asset_id check has been failed");
+        assert_eq!(gotten_price.last_update.unwrap().block_height, env::block_height(), "Get price metadata check has been failed");
+    }
+
+    #[test]
+    fn test_get_and_list_prices() {
+        let (mut near_contract, token_address, _user_account) = init_test_env();
+        let usdt_address: AccountId = "usdt.near".parse().unwrap();
+
+        near_contract.upsert_oracle_price(&Price { asset_id: token_address.clone(), value: 20, volatility: 90 }, 100);
+        near_contract.upsert_oracle_price(&Price { asset_id: usdt_address.clone(), value: 1, volatility: 100 }, 101);
+
+        let prices = near_contract.get_prices(vec![usdt_address.clone(), carol(), token_address.clone()]);
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices[0].asset_id, usdt_address);
+        assert_eq!(prices[0].last_update.as_ref().unwrap().oracle_block_height, 101);
+
+        let listed = near_contract.list_prices(1, 10);
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].asset_id, usdt_address);
+        assert_eq!(near_contract.list_prices(0, 1)[0].asset_id, token_address);
+    }
+
+    #[test]
+    fn test_list_indexed_legacy_prices() {
+        let (mut near_contract, token_address, _user_account) = init_test_env();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+
+        // Released contract stored prices without metadata and the index
+        near_contract.prices.insert(&token_address, &Price { asset_id: token_address.clone(), value: 20, volatility: 90 });
+        assert!(near_contract.list_prices(0, 10).is_empty());
+
+        near_contract.index_prices(vec![token_address.clone(), carol()]);
+
+        let listed = near_contract.list_prices(0, 10);
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].asset_id, token_address);
+        assert_eq!(listed[0].value, U128(20));
+        assert!(listed[0].last_update.is_none());
     }
 }
\ No newline at end of file
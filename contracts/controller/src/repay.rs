@@ -12,12 +12,16 @@ impl Contract {
         true
     }
 
+    /// Records the repayment, borrow_balance is the balance on the market side before the repayment
     pub fn repay_borrows(
         &mut self,
         account_id: AccountId,
         token_address: AccountId,
         token_amount: WBalance,
+        borrow_balance: WBalance,
     )-> Balance{
+        self.assert_market_caller(&token_address);
+        self.sync_borrow_balance(&account_id, &token_address, borrow_balance);
         assert_eq!(
             self.is_repay_allowed(
                 account_id.clone(),
@@ -31,7 +35,10 @@ impl Contract {
             Balance::from(token_amount)
         );
 
-        return self.decrease_borrows(account_id, token_address, token_amount);
+        let existing_borrows: Balance = self.get_entity_by_token(ActionType::Borrow, account_id.clone(), token_address.clone());
+        let repay_amount: Balance = std::cmp::min(existing_borrows, Balance::from(token_amount));
+
+        return self.internal_decrease_borrows(account_id, token_address, U128(repay_amount));
     }
 
 }
\ No newline at end of file
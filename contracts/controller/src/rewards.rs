@@ -0,0 +1,330 @@
+use crate::*;
+
+use near_sdk::{ext_contract, is_promise_success, log, BlockHeight, Promise};
+
+pub use math::REWARD_INDEX_DECIMALS;
+
+#[ext_contract(ext_reward_token)]
+trait RewardTokenInterface {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: WBalance, memo: Option<String>);
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardState {
+    /// Amount of reward tokens distributed per block among the whole side of the market
+    pub emission_per_block: WBalance,
+
+    /// Accumulated rewards per a unit of position, REWARD_INDEX_DECIMALS is 1
+    pub index: WBalance,
+
+    /// BlockHeight of last index update
+    pub last_block: BlockHeight,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MarketRewards {
+    /// Rewards of suppliers
+    pub supply: RewardState,
+
+    /// Rewards of borrowers
+    pub borrow: RewardState,
+
+    /// Sum of all supplies of the market
+    pub total_supplied: WBalance,
+
+    /// Sum of all borrows of the market
+    pub total_borrowed: WBalance,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardIndexes {
+    /// Supply reward index at the moment of last supply change
+    pub supply: WBalance,
+
+    /// Borrow reward index at the moment of last borrow change
+    pub borrow: WBalance,
+}
+
+impl RewardState {
+    fn new() -> Self {
+        RewardState {
+            emission_per_block: U128(0),
+            index: U128(0),
+            last_block: env::block_height(),
+        }
+    }
+
+    /// Returns the index with rewards accrued up to the current block
+    fn get_current_index(&self, total: Balance) -> u128 {
+        let blocks = env::block_height().saturating_sub(self.last_block);
+        Balance::from(self.index) + math::reward_index_increase(self.emission_per_block.into(), blocks, total)
+    }
+
+    fn accrue(&mut self, total: Balance) {
+        self.index = U128(self.get_current_index(total));
+        self.last_block = env::block_height();
+    }
+}
+
+impl MarketRewards {
+    fn new() -> Self {
+        MarketRewards {
+            supply: RewardState::new(),
+            borrow: RewardState::new(),
+            total_supplied: U128(0),
+            total_borrowed: U128(0),
+        }
+    }
+
+    fn get_state(&self, action: &ActionType) -> (&RewardState, Balance) {
+        match action {
+            ActionType::Supply => (&self.supply, self.total_supplied.into()),
+            ActionType::Borrow => (&self.borrow, self.total_borrowed.into()),
+        }
+    }
+}
+
+impl Contract {
+    pub fn get_market_rewards(&self, market: &AccountId) -> MarketRewards {
+        self.market_rewards.get(market).unwrap_or_else(MarketRewards::new)
+    }
+
+    /// Credits rewards accrued on the current position of the account and moves its index snapshot,
+    /// has to be called before every position change. Market totals are moved from balance to new_balance.
+    /// Returns storage allocated for market rewards, it's covered by the contract balance.
+    pub fn distribute_rewards(&mut self, action: &ActionType, account: &AccountId, market: &AccountId, balance: Balance, new_balance: Balance) -> i64 {
+        let initial_storage = env::storage_usage();
+        let index = self.accrue_market_rewards(action, market, balance, new_balance);
+        let contract_storage = env::storage_usage() as i64 - initial_storage as i64;
+
+        self.credit_account_rewards(action, account, market, balance, index);
+        contract_storage
+    }
+
+    fn accrue_market_rewards(&mut self, action: &ActionType, market: &AccountId, balance: Balance, new_balance: Balance) -> u128 {
+        let mut rewards = self.get_market_rewards(market);
+        let index: u128 = match action {
+            ActionType::Supply => {
+                rewards.supply.accrue(rewards.total_supplied.into());
+                rewards.total_supplied = U128(Balance::from(rewards.total_supplied).saturating_sub(balance) + new_balance);
+                rewards.supply.index.into()
+            }
+            ActionType::Borrow => {
+                rewards.borrow.accrue(rewards.total_borrowed.into());
+                rewards.total_borrowed = U128(Balance::from(rewards.total_borrowed).saturating_sub(balance) + new_balance);
+                rewards.borrow.index.into()
+            }
+        };
+        self.market_rewards.insert(market, &rewards);
+        index
+    }
+
+    fn credit_account_rewards(&mut self, action: &ActionType, account: &AccountId, market: &AccountId, balance: Balance, index: u128) {
+        let mut account_indexes = self.account_reward_indexes.get(account).unwrap_or_else(|| {
+            UnorderedMap::new(StorageKeys::AccountRewardIndexesEntry { account_hash: env::sha256(account.as_bytes()) })
+        });
+        let mut indexes = account_indexes.get(market).unwrap_or(RewardIndexes { supply: U128(0), borrow: U128(0) });
+        let account_index: u128 = match action {
+            ActionType::Supply => indexes.supply.into(),
+            ActionType::Borrow => indexes.borrow.into(),
+        };
+
+        let accrued = math::rewards_amount(balance, index, account_index);
+        if accrued > 0 {
+            let existing = self.account_accrued_rewards.get(account).unwrap_or(0);
+            self.account_accrued_rewards.insert(account, &(existing + accrued));
+        }
+
+        match action {
+            ActionType::Supply => indexes.supply = U128(index),
+            ActionType::Borrow => indexes.borrow = U128(index),
+        }
+        account_indexes.insert(market, &indexes);
+        self.account_reward_indexes.insert(account, &account_indexes);
+    }
+
+    /// Sets the NEP-141 token paid as rewards, applied by governance after the timelock
+    pub fn set_reward_token(&mut self, reward_token: AccountId) {
+        events::emit::config_change("reward_token", &reward_token);
+        self.reward_token = Some(reward_token);
+    }
+
+    /// Sets amounts of reward tokens distributed per block among suppliers and borrowers of the market,
+    /// applied by governance after the timelock
+    pub fn set_market_emission(&mut self, market: AccountId, supply_per_block: WBalance, borrow_per_block: WBalance) {
+        let mut rewards = self.get_market_rewards(&market);
+        rewards.supply.accrue(rewards.total_supplied.into());
+        rewards.borrow.accrue(rewards.total_borrowed.into());
+        rewards.supply.emission_per_block = supply_per_block;
+        rewards.borrow.emission_per_block = borrow_per_block;
+
+        events::emit::config_change("market_emission", &rewards);
+        self.market_rewards.insert(&market, &rewards);
+    }
+
+    /// Sets market totals of positions created before rewards accounting was introduced,
+    /// has to be done before enabling emission for such markets, applied by governance after the timelock
+    pub fn set_market_totals(&mut self, market: AccountId, total_supplied: WBalance, total_borrowed: WBalance) {
+        let mut rewards = self.get_market_rewards(&market);
+        rewards.supply.accrue(rewards.total_supplied.into());
+        rewards.borrow.accrue(rewards.total_borrowed.into());
+        rewards.total_supplied = total_supplied;
+        rewards.total_borrowed = total_borrowed;
+
+        events::emit::config_change("market_totals", &rewards);
+        self.market_rewards.insert(&market, &rewards);
+    }
+
+    fn get_pending_rewards(&self, action: &ActionType, account: &AccountId, market: &AccountId) -> Balance {
+        let rewards = self.get_market_rewards(market);
+        let (state, total) = rewards.get_state(action);
+        let account_index: u128 = self
+            .account_reward_indexes
+            .get(account)
+            .and_then(|indexes| indexes.get(market))
+            .map(|indexes| match action {
+                ActionType::Supply => indexes.supply.into(),
+                ActionType::Borrow => indexes.borrow.into(),
+            })
+            .unwrap_or(0);
+        let balance = self.get_entity_by_token(*action, account.clone(), market.clone());
+
+        math::rewards_amount(balance, state.get_current_index(total), account_index)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Transfers rewards accrued on the given markets along with previously accrued ones
+    pub fn claim_rewards(&mut self, markets: Vec<AccountId>) -> Promise {
+        let account = env::predecessor_account_id();
+        let reward_token = self.reward_token.clone().expect("Reward token isn't configured");
+
+        let initial_storage = env::storage_usage();
+        let mut contract_storage: i64 = 0;
+        for market in markets.iter() {
+            for action in [ActionType::Supply, ActionType::Borrow] {
+                let balance = self.get_entity_by_token(action, account.clone(), market.clone());
+                contract_storage += self.distribute_rewards(&action, &account, market, balance, balance);
+            }
+        }
+        self.update_account_storage_except(&account, initial_storage, contract_storage);
+
+        let amount: Balance = self.account_accrued_rewards.get(&account).unwrap_or(0);
+        assert!(amount > 0, "Account {} has no rewards to claim", account);
+        self.account_accrued_rewards.insert(&account, &0);
+
+        ext_reward_token::ft_transfer(
+            account.clone(),
+            U128(amount),
+            Some(format!("Rewards claim with token_amount {}", amount)),
+            reward_token,
+            ONE_YOCTO,
+            TGAS * 10,
+        )
+        .then(ext_self::claim_rewards_callback(
+            account,
+            U128(amount),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            TGAS * 10,
+        ))
+    }
+
+    #[private]
+    pub fn claim_rewards_callback(&mut self, account: AccountId, amount: WBalance) {
+        if !is_promise_success() {
+            log!("Failed to transfer {} reward tokens to {}", Balance::from(amount), account);
+            let existing = self.account_accrued_rewards.get(&account).unwrap_or(0);
+            self.account_accrued_rewards.insert(&account, &(existing + Balance::from(amount)));
+        }
+    }
+
+    /// Returns claimable rewards of the account including ones pending on the given markets
+    pub fn get_account_rewards(&self, account: AccountId, markets: Vec<AccountId>) -> WBalance {
+        let mut amount: Balance = self.account_accrued_rewards.get(&account).unwrap_or(0);
+        for market in markets.iter() {
+            amount += self.get_pending_rewards(&ActionType::Supply, &account, market);
+            amount += self.get_pending_rewards(&ActionType::Borrow, &account, market);
+        }
+        U128(amount)
+    }
+
+    pub fn get_market_rewards_info(&self, market: AccountId) -> MarketRewards {
+        self.get_market_rewards(&market)
+    }
+
+    pub fn get_reward_token(&self) -> Option<AccountId> {
+        self.reward_token.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::register_account;
+
+    fn init() -> (Contract, AccountId) {
+        let mut contract = Contract::new(Config::new(alice(), alice()));
+        let market: AccountId = "dwnear.near".parse().unwrap();
+
+        register_account(&mut contract, &bob());
+        register_account(&mut contract, &carol());
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+        contract.set_market_emission(market.clone(), U128(100), U128(10));
+        (contract, market)
+    }
+
+    fn set_block_height(block_height: BlockHeight) {
+        testing_env!(VMContextBuilder::new().block_index(block_height).build());
+    }
+
+    #[test]
+    fn test_rewards_are_split_by_position_share() {
+        let (mut contract, market) = init();
+
+        contract.internal_increase_supplies(bob(), market.clone(), U128(300));
+        contract.internal_increase_supplies(carol(), market.clone(), U128(100));
+        contract.internal_increase_borrows(carol(), market.clone(), U128(50));
+
+        set_block_height(10);
+        assert_eq!(contract.get_account_rewards(bob(), vec![market.clone()]), U128(750));
+        assert_eq!(contract.get_account_rewards(carol(), vec![market.clone()]), U128(250 + 100));
+
+        // Position change moves the snapshot, accrued rewards are kept
+        contract.internal_decrease_supplies(bob(), market.clone(), U128(300));
+        set_block_height(20);
+        assert_eq!(contract.get_account_rewards(bob(), vec![market.clone()]), U128(750));
+        assert_eq!(contract.get_account_rewards(carol(), vec![market.clone()]), U128(250 + 1000 + 200));
+    }
+
+    #[test]
+    fn test_emission_change_keeps_accrued_index() {
+        let (mut contract, market) = init();
+
+        contract.internal_increase_supplies(bob(), market.clone(), U128(100));
+        set_block_height(10);
+
+        testing_env!(VMContextBuilder::new().block_index(10).predecessor_account_id(alice()).build());
+        contract.set_market_emission(market.clone(), U128(0), U128(0));
+
+        set_block_height(20);
+        assert_eq!(contract.get_account_rewards(bob(), vec![market.clone()]), U128(1000));
+    }
+
+    #[test]
+    #[should_panic(expected = "Reward token isn't configured")]
+    fn failed_claim_rewards_without_reward_token() {
+        let (mut contract, market) = init();
+
+        contract.claim_rewards(vec![market]);
+    }
+}
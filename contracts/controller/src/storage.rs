@@ -0,0 +1,302 @@
+use crate::*;
+
+use near_contract_standards::storage_management::{StorageBalance, StorageBalanceBounds, StorageManagement};
+use near_sdk::{assert_one_yocto, log, Promise, StorageUsage};
+
+/// Storage taken by the account storage record itself
+const ACCOUNT_STORAGE_RECORD_SIZE: StorageUsage = 200;
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct AccountStorage {
+    /// Deposit attached by the account to pay for storage
+    pub deposit: Balance,
+
+    /// Bytes of storage allocated for account positions
+    pub used: StorageUsage,
+
+    /// Part of the deposit paid by the contract for accounts created by the released contract, it isn't refunded
+    pub sponsored: Balance,
+}
+
+impl AccountStorage {
+    pub fn required_balance(&self) -> Balance {
+        Balance::from(self.used + ACCOUNT_STORAGE_RECORD_SIZE) * env::storage_byte_cost()
+    }
+
+    pub fn available_balance(&self) -> Balance {
+        self.deposit.saturating_sub(self.required_balance())
+    }
+}
+
+impl Contract {
+    fn storage_min_balance(&self) -> Balance {
+        Balance::from(ACCOUNT_STORAGE_RECORD_SIZE) * env::storage_byte_cost()
+    }
+
+    fn get_account_storage(&self, account: &AccountId) -> AccountStorage {
+        self.account_storages.get(account).unwrap_or_else(|| {
+            env::panic_str(format!("Account {} isn't registered, storage_deposit is required", account).as_str())
+        })
+    }
+
+    /// Charges the account for storage allocated since initial_storage or releases the freed one
+    pub fn update_account_storage(&mut self, account: &AccountId, initial_storage: StorageUsage) {
+        self.update_account_storage_except(account, initial_storage, 0);
+    }
+
+    /// Same as update_account_storage, except contract_storage allocated for contract-wide entries
+    /// in between, which is covered by the contract balance
+    pub fn update_account_storage_except(&mut self, account: &AccountId, initial_storage: StorageUsage, contract_storage: i64) {
+        let mut storage = self.get_account_storage(account);
+        let account_storage = env::storage_usage() as i64 - initial_storage as i64 - contract_storage;
+
+        if account_storage >= 0 {
+            storage.used += account_storage as StorageUsage;
+        } else {
+            storage.used = storage.used.saturating_sub(account_storage.unsigned_abs());
+        }
+
+        assert!(
+            storage.required_balance() <= storage.deposit,
+            "Not enough storage deposit for account {}, required {}",
+            account,
+            storage.required_balance()
+        );
+        self.account_storages.insert(account, &storage);
+    }
+
+    /// Registers the account which got positions from the released contract, the minimum balance is paid by the contract
+    pub fn register_legacy_account(&mut self, account: &AccountId) {
+        if self.account_storages.get(account).is_none() {
+            let min_balance = self.storage_min_balance();
+            self.account_storages.insert(account, &AccountStorage { deposit: min_balance, used: 0, sponsored: min_balance });
+        }
+    }
+
+    fn has_positions(&self, account: &AccountId) -> bool {
+        let has_balance = |map: Option<UnorderedMap<AccountId, Balance>>| {
+            map.map(|map| map.values().any(|balance| balance > 0)).unwrap_or(false)
+        };
+        has_balance(self.account_supplies.get(account)) || has_balance(self.account_borrows.get(account))
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>) -> StorageBalance {
+        let amount: Balance = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+
+        let storage = match self.account_storages.get(&account_id) {
+            Some(mut storage) => {
+                if registration_only {
+                    log!("The account is already registered, refunding the deposit");
+                    if amount > 0 {
+                        Promise::new(env::predecessor_account_id()).transfer(amount);
+                    }
+                } else {
+                    storage.deposit += amount;
+                }
+                storage
+            }
+            None => {
+                let min_balance = self.storage_min_balance();
+                assert!(amount >= min_balance, "The attached deposit is less than the minimum storage balance");
+
+                let deposit = if registration_only { min_balance } else { amount };
+                if amount > deposit {
+                    Promise::new(env::predecessor_account_id()).transfer(amount - deposit);
+                }
+                AccountStorage { deposit, used: 0, sponsored: 0 }
+            }
+        };
+
+        self.account_storages.insert(&account_id, &storage);
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut storage = self.get_account_storage(&account_id);
+
+        let available = storage.available_balance();
+        let amount: Balance = amount.map(Balance::from).unwrap_or(available);
+        assert!(amount <= available, "The amount is greater than the available storage balance");
+
+        storage.deposit -= amount;
+        self.account_storages.insert(&account_id, &storage);
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Releases account storage once all of its positions are closed.
+    /// Unclaimed rewards are forfeited with force, otherwise they have to be claimed first.
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let force = force.unwrap_or(false);
+
+        let storage = match self.account_storages.get(&account_id) {
+            Some(storage) => storage,
+            None => {
+                log!("The account {} is not registered", account_id);
+                return false;
+            }
+        };
+        assert!(
+            !self.has_legacy_positions(&account_id),
+            "Positions of {} have to be migrated first",
+            account_id
+        );
+        assert!(!self.has_positions(&account_id), "Can't unregister the account with open positions");
+
+        let accrued_rewards = self.account_accrued_rewards.get(&account_id).unwrap_or(0);
+        assert!(
+            force || accrued_rewards == 0,
+            "Can't unregister the account with unclaimed rewards, claim them or use force to forfeit"
+        );
+        if accrued_rewards > 0 {
+            log!("Account {} forfeits {} unclaimed reward tokens", account_id, accrued_rewards);
+        }
+
+        if let Some(mut supplies) = self.account_supplies.remove(&account_id) {
+            supplies.clear();
+        }
+        if let Some(mut borrows) = self.account_borrows.remove(&account_id) {
+            borrows.clear();
+        }
+        if let Some(mut collaterals) = self.account_collaterals.remove(&account_id) {
+            collaterals.clear();
+        }
+        if let Some(mut reward_indexes) = self.account_reward_indexes.remove(&account_id) {
+            reward_indexes.clear();
+        }
+        self.account_accrued_rewards.remove(&account_id);
+        self.borrowers.remove(&account_id);
+        self.account_storages.remove(&account_id);
+
+        Promise::new(account_id).transfer(storage.deposit - storage.sponsored + 1);
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: self.storage_min_balance().into(),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.account_storages.get(&account_id).map(|storage| StorageBalance {
+            total: storage.deposit.into(),
+            available: storage.available_balance().into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, BlockHeight};
+
+    use super::*;
+    use crate::test_utils::register_account;
+
+    fn init() -> (Contract, AccountId) {
+        let mut contract = Contract::new(Config::new(alice(), alice()));
+        let market: AccountId = "dwnear.near".parse().unwrap();
+        contract.set_market_emission(market.clone(), U128(100), U128(10));
+        (contract, market)
+    }
+
+    fn set_context(account: AccountId, block_height: BlockHeight) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account)
+            .attached_deposit(1)
+            .block_index(block_height)
+            .build());
+    }
+
+    /// Closes a position which accrued rewards for a few blocks
+    fn init_with_rewards() -> Contract {
+        let (mut contract, market) = init();
+        register_account(&mut contract, &carol());
+
+        contract.internal_increase_supplies(carol(), market.clone(), U128(100));
+        set_context(carol(), 10);
+        contract.internal_decrease_supplies(carol(), market, U128(100));
+        assert!(contract.account_accrued_rewards.get(&carol()).unwrap_or(0) > 0);
+        contract
+    }
+
+    #[test]
+    fn test_unregister_with_force_forfeits_rewards() {
+        let mut contract = init_with_rewards();
+
+        assert!(contract.storage_unregister(Some(true)));
+        assert!(contract.storage_balance_of(carol()).is_none());
+        assert!(contract.account_accrued_rewards.get(&carol()).is_none());
+        assert!(contract.account_reward_indexes.get(&carol()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't unregister the account with unclaimed rewards")]
+    fn failed_unregister_with_rewards() {
+        let mut contract = init_with_rewards();
+
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn test_account_is_charged_for_own_storage_only() {
+        let (mut contract, _market) = init();
+        let market: AccountId = "dweth.near".parse().unwrap();
+        let frank: AccountId = "frank.near".parse().unwrap();
+        register_account(&mut contract, &carol());
+        register_account(&mut contract, &frank);
+
+        // The first position creates market rewards, the borrowers index grows with every borrower
+        for account in [carol(), frank.clone()] {
+            contract.internal_increase_supplies(account.clone(), market.clone(), U128(100));
+            contract.internal_increase_borrows(account, market.clone(), U128(10));
+        }
+
+        let used = |account: &AccountId| contract.account_storages.get(account).unwrap().used;
+        assert!(used(&carol()) > 0);
+        assert_eq!(used(&carol()), used(&frank));
+    }
+
+    #[test]
+    fn test_legacy_account_is_registered_on_migration() {
+        let (mut contract, market) = init();
+
+        // Released contract created maps of all accounts with the same prefix and had no storage records
+        let mut supplies: UnorderedMap<AccountId, Balance> = UnorderedMap::new(StorageKeys::SuppliesToken);
+        supplies.insert(&market, &100);
+        contract.account_supplies.insert(&carol(), &supplies);
+
+        contract.migrate_account_positions(vec![PositionRecord {
+            account_id: carol(),
+            token_address: market.clone(),
+            supplied: U128(100),
+            borrowed: U128(10),
+        }]);
+
+        let storage = contract.storage_balance_of(carol()).unwrap();
+        assert_eq!(storage.total, contract.storage_balance_bounds().min);
+        assert!(contract.is_collateral(&carol(), &market));
+
+        contract.internal_decrease_borrows(carol(), market.clone(), U128(10));
+        contract.internal_decrease_supplies(carol(), market, U128(100));
+        set_context(carol(), 0);
+        assert!(contract.storage_unregister(None));
+    }
+}
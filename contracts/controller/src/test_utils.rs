@@ -1,14 +1,12 @@
-// use near_sdk::AccountId;
-// use near_sdk::test_utils::test_env::{alice, bob, carol};
-
-// use crate::{Config, Contract};
-
-// pub fn init_test_env() -> (Contract, AccountId, AccountId) {
-//     let (owner_account, oracle_account, user_account) = (alice(), bob(), carol());
-
-//     let eth_contract = Contract::new(Config { owner_id: owner_account, oracle_account_id: oracle_account });
-
-//     let token_address: AccountId = "near".parse().unwrap();
-
-//     return (eth_contract, token_address, user_account);
-// }
\ No newline at end of file
+use near_contract_standards::storage_management::StorageManagement;
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{testing_env, AccountId, ONE_NEAR};
+
+use crate::Contract;
+
+/// Registers the account in the contract storage as storage_deposit would do on-chain
+pub fn register_account(contract: &mut Contract, account: &AccountId) {
+    testing_env!(VMContextBuilder::new().predecessor_account_id(account.clone()).attached_deposit(ONE_NEAR).build());
+    contract.storage_deposit(None, None);
+    testing_env!(VMContextBuilder::new().build());
+}
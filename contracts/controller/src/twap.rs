@@ -0,0 +1,219 @@
+use crate::*;
+
+use near_sdk::BlockHeight;
+
+/// Number of observations kept per asset, they cover two TWAP windows
+pub const MAX_PRICE_OBSERVATIONS: usize = 64;
+
+/// TWAP window set on initialization, about 30 minutes of 1 second blocks
+pub const DEFAULT_TWAP_WINDOW: BlockHeight = 30 * 60;
+
+/// Price used for borrow capacity and liquidations
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum PriceMode {
+    /// Last price pushed by the oracle
+    Spot,
+    /// Time-weighted average price over the TWAP window
+    Twap,
+    /// The lowest of spot price and TWAP
+    MinSpotTwap,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceObservation {
+    /// BlockHeight of the observation
+    pub block: BlockHeight,
+
+    /// Cumulative price at the observation block
+    pub cumulative_price: WBalance,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceAccumulator {
+    /// Sum of prices multiplied by the number of blocks they were valid for, wraps on overflow
+    pub cumulative_price: WBalance,
+
+    /// Price valid since the last update
+    pub last_price: WBalance,
+
+    /// BlockHeight of last update
+    pub last_block: BlockHeight,
+
+    /// Cumulative prices recorded at least every window / MAX_PRICE_OBSERVATIONS * 2 blocks, oldest first
+    pub observations: Vec<PriceObservation>,
+}
+
+impl PriceAccumulator {
+    fn new(price: Balance) -> Self {
+        PriceAccumulator {
+            cumulative_price: U128(0),
+            last_price: U128(price),
+            last_block: env::block_height(),
+            observations: vec![PriceObservation { block: env::block_height(), cumulative_price: U128(0) }],
+        }
+    }
+
+    /// Cumulative price up to the current block
+    fn get_current_cumulative_price(&self) -> u128 {
+        let blocks = env::block_height().saturating_sub(self.last_block);
+        Balance::from(self.cumulative_price).wrapping_add(Balance::from(self.last_price).wrapping_mul(blocks as u128))
+    }
+
+    fn update(&mut self, price: Balance, window: BlockHeight) {
+        let current_block = env::block_height();
+        self.cumulative_price = U128(self.get_current_cumulative_price());
+        self.last_price = U128(price);
+        self.last_block = current_block;
+
+        let period = std::cmp::max(window * 2 / MAX_PRICE_OBSERVATIONS as u64, 1);
+        let last_observation_block = self.observations.last().map(|observation| observation.block).unwrap_or(0);
+        if current_block >= last_observation_block + period {
+            if self.observations.len() >= MAX_PRICE_OBSERVATIONS {
+                self.observations.remove(0);
+            }
+            self.observations.push(PriceObservation { block: current_block, cumulative_price: self.cumulative_price });
+        }
+    }
+
+    /// Average price since the latest observation made at least window blocks ago,
+    /// the oldest observation is used if there is no such one yet
+    fn get_twap(&self, window: BlockHeight) -> Balance {
+        let current_block = env::block_height();
+        let target_block = current_block.saturating_sub(window);
+        let observation = self
+            .observations
+            .iter()
+            .rev()
+            .find(|observation| observation.block <= target_block)
+            .or_else(|| self.observations.first());
+
+        match observation {
+            Some(observation) if observation.block < current_block => {
+                let cumulative_change = self.get_current_cumulative_price().wrapping_sub(observation.cumulative_price.into());
+                cumulative_change / (current_block - observation.block) as u128
+            }
+            _ => self.last_price.into(),
+        }
+    }
+}
+
+impl Contract {
+    /// Updates the cumulative price of the asset, called on every price update
+    pub fn record_price(&mut self, price: &Price) {
+        let mut accumulator = self
+            .price_accumulators
+            .get(&price.asset_id)
+            .unwrap_or_else(|| PriceAccumulator::new(price.value));
+        accumulator.update(price.value, self.twap_window);
+        self.price_accumulators.insert(&price.asset_id, &accumulator);
+    }
+
+    pub fn get_twap(&self, asset_id: &AccountId) -> Option<Balance> {
+        self.price_accumulators
+            .get(asset_id)
+            .map(|accumulator| accumulator.get_twap(self.twap_window))
+    }
+
+    /// Price of the asset according to the price mode, used for health factor and liquidations
+    pub fn get_risk_price(&self, asset_id: AccountId) -> Option<Price> {
+        let mut price = self.get_spot_price(&asset_id)?;
+        let twap = self.get_twap(&asset_id).unwrap_or(price.value);
+
+        price.value = match self.price_mode {
+            PriceMode::Spot => price.value,
+            PriceMode::Twap => twap,
+            PriceMode::MinSpotTwap => std::cmp::min(price.value, twap),
+        };
+        Some(price)
+    }
+
+    /// Sets the price mode and TWAP window, applied by governance after the timelock
+    pub fn set_price_mode(&mut self, mode: PriceMode, twap_window: BlockHeight) {
+        assert!(twap_window > 0, "TWAP window should be a positive number");
+
+        events::emit::config_change("price_mode", &mode);
+        events::emit::config_change("twap_window", &twap_window);
+        self.price_mode = mode;
+        self.twap_window = twap_window;
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_twap_price(&self, asset_id: AccountId) -> Option<WBalance> {
+        self.get_twap(&asset_id).map(U128)
+    }
+
+    pub fn get_price_mode(&self) -> PriceMode {
+        self.price_mode
+    }
+
+    pub fn get_twap_window(&self) -> BlockHeight {
+        self.twap_window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::alice;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    fn set_block_height(block_height: BlockHeight) {
+        testing_env!(VMContextBuilder::new().block_index(block_height).build());
+    }
+
+    fn set_price(contract: &mut Contract, asset_id: &AccountId, value: Balance) {
+        contract.upsert_price(&Price { asset_id: asset_id.clone(), value, volatility: 100 });
+    }
+
+    fn init() -> (Contract, AccountId) {
+        set_block_height(0);
+        let mut contract = Contract::new(Config::new(alice(), alice()));
+        contract.set_price_mode(PriceMode::Twap, 100);
+        let asset_id: AccountId = "dwnear.near".parse().unwrap();
+
+        set_price(&mut contract, &asset_id, 10);
+        (contract, asset_id)
+    }
+
+    #[test]
+    fn test_twap_over_window() {
+        let (mut contract, asset_id) = init();
+
+        set_block_height(50);
+        set_price(&mut contract, &asset_id, 30);
+
+        // Window isn't filled yet, average since the first price
+        set_block_height(100);
+        assert_eq!(contract.get_twap(&asset_id), Some((10 * 50 + 30 * 50) / 100));
+
+        // Observation at block 50 is the latest one made at least 100 blocks ago
+        set_block_height(150);
+        assert_eq!(contract.get_twap(&asset_id), Some(30));
+    }
+
+    #[test]
+    fn test_one_block_spike_is_ignored() {
+        let (mut contract, asset_id) = init();
+
+        set_block_height(200);
+        set_price(&mut contract, &asset_id, 1000);
+
+        set_block_height(201);
+        assert_eq!(contract.get_risk_price(asset_id.clone()).unwrap().value, (10 * 200 + 1000) / 201);
+
+        contract.set_price_mode(PriceMode::MinSpotTwap, 100);
+        set_price(&mut contract, &asset_id, 5);
+        assert_eq!(contract.get_risk_price(asset_id.clone()).unwrap().value, 5);
+
+        contract.set_price_mode(PriceMode::Spot, 100);
+        assert_eq!(contract.get_risk_price(asset_id).unwrap().value, 5);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::*;
+
+use near_sdk::IntoStorageKey;
+
+/// Version of the contract state layout, has to be bumped once per release which changes Contract fields or Config.
+/// Fields added since the released contract ship together as version 1, migrate converts ContractV0 into it.
+pub const STATE_VERSION: u32 = 1;
+
+/// Gas reserved for the upgrade method itself, the rest is attached to the migrate call
+#[cfg(target_arch = "wasm32")]
+const GAS_FOR_UPGRADE: near_sdk::Gas = near_sdk::Gas(20_000_000_000_000);
+
+/// Config layout of the released contract, without liquidation limits
+#[derive(BorshDeserialize)]
+#[cfg_attr(test, derive(BorshSerialize))]
+pub struct ConfigV0 {
+    owner_id: AccountId,
+    oracle_account_id: AccountId,
+}
+
+impl From<ConfigV0> for Config {
+    fn from(old: ConfigV0) -> Self {
+        Config::new(old.owner_id, old.oracle_account_id)
+    }
+}
+
+/// Contract layout of the released contract, written before state versioning
+#[derive(BorshDeserialize)]
+#[cfg_attr(test, derive(BorshSerialize))]
+pub struct ContractV0 {
+    markets: LookupMap<AccountId, AccountId>,
+    account_supplies: LookupMap<AccountId, UnorderedMap<AccountId, Balance>>,
+    account_borrows: LookupMap<AccountId, UnorderedMap<AccountId, Balance>>,
+    prices: LookupMap<AccountId, Price>,
+    config: LazyOption<ConfigV0>,
+}
+
+impl From<ContractV0> for Contract {
+    fn from(old: ContractV0) -> Self {
+        let config: Config = old.config.get().expect("Config is not initialized").into();
+
+        Self {
+            markets: old.markets,
+            account_supplies: old.account_supplies,
+            account_borrows: old.account_borrows,
+            prices: old.prices,
+            config: LazyOption::new(StorageKeys::Config, Some(&config)),
+            account_collaterals: LookupMap::new(StorageKeys::Collaterals),
+            account_storages: LookupMap::new(StorageKeys::AccountStorages),
+            market_rewards: LookupMap::new(StorageKeys::MarketRewards),
+            account_reward_indexes: LookupMap::new(StorageKeys::AccountRewardIndexes),
+            account_accrued_rewards: LookupMap::new(StorageKeys::AccountAccruedRewards),
+            reward_token: None,
+            governance_delay: DEFAULT_GOVERNANCE_DELAY,
+            governance_proposals: UnorderedMap::new(StorageKeys::GovernanceProposals),
+            next_proposal_id: 0,
+            keepers: UnorderedSet::new(StorageKeys::Keepers),
+            price_accumulators: LookupMap::new(StorageKeys::PriceAccumulators),
+            price_mode: PriceMode::Spot,
+            twap_window: DEFAULT_TWAP_WINDOW,
+            borrowers: UnorderedSet::new(StorageKeys::Borrowers),
+            price_updates: UnorderedMap::new(StorageKeys::PriceUpdates),
+            listed_markets: UnorderedSet::new(StorageKeys::ListedMarkets),
+            price_assets: UnorderedSet::new(StorageKeys::PriceAssets),
+        }
+    }
+}
+
+impl Contract {
+    /// Returns the version of stored state, 0 stands for the state written before versioning
+    pub fn read_state_version() -> u32 {
+        env::storage_read(&StorageKeys::StateVersion.into_storage_key())
+            .map(|bytes| u32::try_from_slice(&bytes).expect("State version is corrupted"))
+            .unwrap_or(0)
+    }
+
+    pub fn write_state_version(version: u32) {
+        env::storage_write(&StorageKeys::StateVersion.into_storage_key(), &version.try_to_vec().unwrap());
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_state_version(&self) -> u32 {
+        Self::read_state_version()
+    }
+
+    /// Converts the state written by a previous version of the contract into the current layout.
+    /// Called by upgrade right after the new code has been deployed.
+    #[init(ignore_state)]
+    #[private]
+    pub fn migrate() -> Self {
+        let version = Self::read_state_version();
+        assert!(
+            version <= STATE_VERSION,
+            "Can't migrate from state version {} to older version {}",
+            version,
+            STATE_VERSION
+        );
+
+        let contract: Contract = match version {
+            0 => {
+                let old: ContractV0 = env::state_read().expect("Contract is not initialized");
+                old.into()
+            }
+            _ => env::state_read().expect("Contract is not initialized"),
+        };
+
+        Self::write_state_version(STATE_VERSION);
+        events::emit::config_change("state_version", &STATE_VERSION);
+        contract
+    }
+}
+
+/// Deploys the code passed as raw input and migrates the state, allowed to be called by admin only.
+/// Exported without near_bindgen, so the code isn't deserialized from JSON.
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+pub extern "C" fn upgrade() {
+    env::setup_panic_hook();
+
+    let contract: Contract = env::state_read().expect("Contract is not initialized");
+    contract.assert_owner();
+
+    let code = env::input().expect("Contract code is required");
+    let required_gas = env::used_gas() + GAS_FOR_UPGRADE;
+    let migrate_gas = env::prepaid_gas().0.checked_sub(required_gas.0).unwrap_or_else(|| {
+        env::panic_str(&format!("Not enough gas attached to upgrade, more than {} is required", required_gas.0))
+    });
+    near_sdk::Promise::new(env::current_account_id())
+        .deploy_contract(code)
+        .function_call(
+            "migrate".to_string(),
+            Vec::new(),
+            NO_DEPOSIT,
+            near_sdk::Gas(migrate_gas),
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+
+    use super::*;
+
+    #[test]
+    fn test_migrate_unversioned_state() {
+        let token_address: AccountId = "near".parse().unwrap();
+
+        // Released contract kept every account's balances under the same prefix
+        let mut supplies: UnorderedMap<AccountId, Balance> = UnorderedMap::new(StorageKeys::SuppliesToken);
+        supplies.insert(&token_address, &100);
+        let mut account_supplies = LookupMap::new(StorageKeys::Supplies);
+        account_supplies.insert(&carol(), &supplies);
+
+        let mut borrows: UnorderedMap<AccountId, Balance> = UnorderedMap::new(StorageKeys::BorrowsToken);
+        borrows.insert(&token_address, &10);
+        let mut account_borrows = LookupMap::new(StorageKeys::Borrows);
+        account_borrows.insert(&carol(), &borrows);
+
+        env::state_write(&ContractV0 {
+            markets: LookupMap::new(StorageKeys::Markets),
+            account_supplies,
+            account_borrows,
+            prices: LookupMap::new(StorageKeys::Prices),
+            config: LazyOption::new(StorageKeys::Config, Some(&ConfigV0 { owner_id: alice(), oracle_account_id: bob() })),
+        });
+        // State written before versioning has no version record
+        assert_eq!(Contract::read_state_version(), 0);
+
+        let contract = Contract::migrate();
+
+        assert_eq!(contract.get_state_version(), STATE_VERSION);
+        assert_eq!(contract.get_entity_by_token(ActionType::Supply, carol(), token_address.clone()), 100);
+        assert_eq!(contract.get_entity_by_token(ActionType::Borrow, carol(), token_address.clone()), 10);
+        assert_eq!(contract.get_contract_config().owner_id, alice());
+        assert_eq!(contract.get_contract_config().oracle_account_id, bob());
+        assert_eq!(contract.get_reward_token(), None);
+        assert_eq!(contract.get_governance_delay(), DEFAULT_GOVERNANCE_DELAY);
+        assert!(contract.get_keepers().is_empty());
+        assert_eq!(contract.get_price_mode(), PriceMode::Spot);
+        assert_eq!(contract.get_borrowers_count(), 0);
+        assert!(contract.list_prices(0, 10).is_empty());
+        assert!(contract.get_markets().is_empty());
+        assert_eq!(contract.get_contract_config().max_close_factor, Config::new(alice(), bob()).max_close_factor);
+    }
+
+    #[test]
+    fn test_migrate_current_state() {
+        let contract = Contract::new(Config::new(alice(), bob()));
+        env::state_write(&contract);
+
+        let contract = Contract::migrate();
+
+        assert_eq!(contract.get_state_version(), STATE_VERSION);
+        assert_eq!(contract.get_contract_config().owner_id, alice());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't migrate from state version")]
+    fn failed_migrate_newer_state() {
+        let contract = Contract::new(Config::new(alice(), bob()));
+        env::state_write(&contract);
+        Contract::write_state_version(STATE_VERSION + 1);
+
+        Contract::migrate();
+    }
+}
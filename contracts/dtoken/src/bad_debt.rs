@@ -0,0 +1,29 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Removes uncollectable borrow of the account from total borrows.
+    /// Shortfall is covered from reserves first, the rest lowers the exchange rate for all suppliers.
+    pub fn write_off_bad_debt(&mut self, account: AccountId) -> WBalance {
+        self.assert_controller();
+        self.assert_no_flash_loan();
+        self.assert_cash_synced();
+        self.accrue_interest();
+
+        let debt: Balance = self.get_borrows_by_account(account.clone());
+        assert!(debt > 0, "Account {} has no borrows", account);
+        self.decrease_borrows(account.clone(), U128(debt));
+
+        let covered_by_reserves: Balance = std::cmp::min(self.total_reserves, debt);
+        self.total_reserves -= covered_by_reserves;
+
+        events::emit::bad_debt_write_off(
+            &account,
+            &self.get_contract_address(),
+            debt,
+            covered_by_reserves,
+            debt - covered_by_reserves,
+        );
+        U128(debt)
+    }
+}
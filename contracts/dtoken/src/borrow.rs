@@ -1,12 +1,68 @@
 use crate::*;
 
+impl Contract {
+    pub fn decrease_borrows(
+        &mut self,
+        account: AccountId,
+        token_amount: WBalance,
+    ) -> Balance {
+        let existing_borrows: Balance = self.get_borrows_by_account(account.clone());
+
+        assert!(existing_borrows >= Balance::from(token_amount), "Repay amount is more than existing borrows");
+        let decreased_borrows: Balance = existing_borrows - Balance::from(token_amount);
+
+        let new_borrows = self.total_borrows.overflowing_sub(Balance::from(token_amount));
+        assert_eq!(new_borrows.1, false, "Overflow occurs while decreasing total supply");
+        self.total_borrows = new_borrows.0;
+        
+        self.borrow_indexes.insert(&account, &self.borrow_index);
+        return self.set_borrows(account.clone(), U128(decreased_borrows));
+    }
+
+    pub fn increase_borrows(
+        &mut self,
+        account: AccountId,
+        token_amount: WBalance,
+    ) -> Balance {
+        let existing_borrows: Balance = self.get_borrows_by_account(account.clone());
+        let increased_borrows: Balance = existing_borrows + Balance::from(token_amount);
+
+        let new_borrows = self.total_borrows.overflowing_add(Balance::from(token_amount));
+        assert_eq!(new_borrows.1, false, "Overflow occurs while incresing total supply");
+        self.total_borrows = new_borrows.0;
+
+        self.borrow_indexes.insert(&account, &self.borrow_index);
+        return self.set_borrows(account.clone(), U128(increased_borrows));
+    }
+
+    /// Borrow index at the moment of last borrow change. Borrows recorded by the released contract have no snapshot,
+    /// they accrue interest since the market index was started on migration.
+    pub fn get_account_borrow_index(&self, account: &AccountId) -> u128 {
+        self.borrow_indexes.get(account).unwrap_or(INITIAL_BORROW_INDEX)
+    }
+
+    fn set_borrows(&mut self, account: AccountId, token_amount: WBalance) -> Balance {
+        self.borrows
+            .insert(&account, &Balance::from(token_amount));
+        return Balance::from(token_amount);
+    }
+}
+
 #[near_bindgen]
 impl Contract {
     pub fn borrow(&mut self, token_amount: WBalance) -> Promise {
+        self.assert_registered(&env::signer_account_id());
+        self.assert_no_flash_loan();
+        self.assert_cash_synced();
+        self.accrue_interest();
+        self.record_action(&env::signer_account_id(), true);
+        // Controller records the borrow with interest accrued since the last change
+        let borrow_balance = self.get_borrows_by_account(env::signer_account_id());
         return controller::make_borrow(
             env::signer_account_id(),
             self.get_contract_address(),
             token_amount,
+            U128(borrow_balance),
             self.get_controller_address(),
             NO_DEPOSIT,
             self.terra_gas(10),
@@ -19,18 +75,17 @@ impl Contract {
         ));
     }
 
+    #[private]
     pub fn make_borrow_callback(
         &mut self,
         token_amount: WBalance,
     ) ->Promise {
         assert_eq!(is_promise_success(), true, "Failed to increase borrow for {} with token amount {}", env::signer_account_id(), Balance::from(token_amount));
 
-        underlying_token::ft_transfer(
+        self.transfer_underlying(
             env::signer_account_id(),
             token_amount,
-            Some(format!("Borrow with token_amount {}", Balance::from(token_amount))),
-            self.get_underlying_contract_address(),
-            ONE_YOCTO,
+            format!("Borrow with token_amount {}", Balance::from(token_amount)),
             self.terra_gas(40),
         )
         .then(ext_self::borrow_ft_transfer_callback(
@@ -41,12 +96,15 @@ impl Contract {
         ))
     }
 
+    #[private]
     pub fn borrow_ft_transfer_callback(
         &mut self,
         token_amount: WBalance,
     ) {
         if is_promise_success(){
             self.increase_borrows(env::signer_account_id(), token_amount);
+            self.total_cash = self.total_cash.saturating_sub(Balance::from(token_amount));
+            events::emit::borrow(&env::signer_account_id(), token_amount.into());
         } 
         else {
             log!("Failed to transfer tokens from {} to user {} with token amount {}", self.get_contract_address(), env::signer_account_id(), Balance::from(token_amount));
@@ -66,6 +124,7 @@ impl Contract {
         }
     }
 
+    #[private]
     pub fn controller_decrease_borrows_callback(&mut self){
         if !is_promise_success(){
             log!("Failed to decrease borrows for {}", env::signer_account_id());
@@ -73,49 +132,14 @@ impl Contract {
         }
     }
 
-    pub fn decrease_borrows(
-        &mut self,
-        account: AccountId,
-        token_amount: WBalance,
-    ) -> Balance {
-        let existing_borrows: Balance = self.get_borrows_by_account(account.clone());
-
-        assert!(existing_borrows >= Balance::from(token_amount), "Repay amount is more than existing borrows");
-        let decreased_borrows: Balance = existing_borrows - Balance::from(token_amount);
-
-        let new_borrows = self.total_borrows.overflowing_sub(Balance::from(token_amount));
-        assert_eq!(new_borrows.1, false, "Overflow occurs while decreasing total supply");
-        self.total_borrows = new_borrows.0;
-        
-        return self.set_borrows(account.clone(), U128(decreased_borrows));
-    }
-
-    pub fn increase_borrows(
-        &mut self,
-        account: AccountId,
-        token_amount: WBalance,
-    ) -> Balance {
-        let existing_borrows: Balance = self.get_borrows_by_account(account.clone());
-        let increased_borrows: Balance = existing_borrows + Balance::from(token_amount);
-
-        let new_borrows = self.total_borrows.overflowing_add(Balance::from(token_amount));
-        assert_eq!(new_borrows.1, false, "Overflow occurs while incresing total supply");
-        self.total_borrows = new_borrows.0;
-        return self.set_borrows(account.clone(), U128(increased_borrows));
-    }
-
-    #[private]
-    pub fn set_borrows(&mut self, account: AccountId, token_amount: WBalance) -> Balance {
-        self.borrows
-            .insert(&account, &Balance::from(token_amount));
-        return Balance::from(token_amount);
-    }
-
+    /// Returns borrow balance with interest accrued since the last balance change
     pub fn get_borrows_by_account(&self, account: AccountId) -> Balance{
         if self.borrows.get(&account).is_none(){
             return 0;
         }
-        return self.borrows.get(&account).unwrap();
+        let principal: Balance = self.borrows.get(&account).unwrap();
+        let account_index: u128 = self.get_account_borrow_index(&account);
+        return math::borrow_balance(principal, self.borrow_index, account_index);
     }
 
 }
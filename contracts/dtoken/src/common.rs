@@ -1,5 +1,59 @@
 use crate::*;
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MarketInfo {
+    /// The account ID of underlying_token
+    pub underlying_token_id: AccountId,
+
+    /// Underlying tokens tracked by market actions, tokens transferred to the market directly aren't included.
+    /// Exchange rate and utilization are computed with it.
+    pub tracked_cash: WBalance,
+
+    /// Total sum of borrowed tokens
+    pub total_borrows: WBalance,
+
+    /// Total sum of reserved tokens
+    pub total_reserves: WBalance,
+
+    /// Total supply of dtokens
+    pub total_supply: WBalance,
+
+    /// Current exchange rate between dtokens and underlying tokens
+    pub exchange_rate: WBalance,
+
+    /// Utilization rate, RATIO_DECIMALS is 100%
+    pub utilization: WRatio,
+
+    /// Current annual borrow rate, RATIO_DECIMALS is 100%
+    pub borrow_apy: WRatio,
+
+    /// Current annual supply rate, RATIO_DECIMALS is 100%
+    pub supply_apy: WRatio,
+
+    /// BlockHeight of last interest accrual
+    pub accrual_block_number: BlockHeight,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountInfo {
+    /// Dtoken balance of the account
+    pub dtoken_balance: WBalance,
+
+    /// Underlying value of dtoken balance at the current exchange rate
+    pub underlying_balance: WBalance,
+
+    /// Borrow balance with accrued interest
+    pub borrow_balance: WBalance,
+
+    /// BlockHeight of last action user produced
+    pub last_action_block: Option<BlockHeight>,
+
+    /// Borrow index at the moment of last borrow balance change
+    pub borrow_index: WBalance,
+}
+
 impl Contract {
 
     pub fn get_controller_address(&self) -> AccountId {
@@ -12,6 +66,22 @@ impl Contract {
         return env::current_account_id().clone();
     }
 
+    pub fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.get_contract_config().owner_id,
+            "This functionality is allowed to be called by admin only"
+        );
+    }
+
+    pub fn assert_controller(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.get_controller_address(),
+            "This functionality is allowed to be called by controller only"
+        );
+    }
+
     pub fn get_signer_address(&self) -> AccountId {
         return env::signer_account_id().clone();
     }
@@ -21,54 +91,107 @@ impl Contract {
     }
 
     pub fn get_exchange_rate(&self, underlying_balance: WBalance) -> Balance {
-        if self.token.total_supply == 0 {
-            return self.initial_exchange_rate;
-        }
-        return (Balance::from(underlying_balance) + self.total_borrows - self.total_reserves)
-            / self.token.total_supply;
+        math::exchange_rate(
+            underlying_balance.into(),
+            self.total_borrows,
+            self.total_reserves,
+            self.token.total_supply,
+            self.initial_exchange_rate,
+        )
     }
 
     pub fn terra_gas(&self, gas: u64) -> Gas {
         return TGAS * gas;
     }
 
-    
-}
+    /// Stores current block height as the last action of the account.
+    /// Guarded actions are refused if the account already acted in this block and the guard is on.
+    pub fn record_action(&mut self, account: &AccountId, guarded: bool) {
+        let current_block: BlockHeight = env::block_height();
+
+        if guarded && self.same_block_guard {
+            assert_ne!(
+                self.actions.get(account),
+                Some(current_block),
+                "Action for account {} is not allowed in the same block as the previous one",
+                account
+            );
+        }
 
-#[near_bindgen]
-impl Contract {
+        self.actions.insert(account, &current_block);
+    }
 
-    pub fn get_total_borrows(&self) -> Balance {
-        return self.total_borrows;
+    pub fn mint(&mut self, account_id: &AccountId, amount: WBalance) {
+        self.assert_registered(account_id);
+        self.token.internal_deposit(&account_id, amount.into());
     }
 
-    pub fn get_total_reserves(&self) -> Balance {
-        return self.total_reserves;
+    pub fn burn(&mut self, account_id: &AccountId, amount: WBalance) {
+        if !self.token.accounts.contains_key(&account_id.clone()) {
+            panic!("User with account {} wasn't found", account_id.clone().to_string());
+        }
+        self.token.internal_withdraw(&account_id, amount.into());
     }
 
-    #[private]
     pub fn set_total_reserves(&mut self, amount: Balance) -> Balance {
         self.total_reserves = amount;
         return self.get_total_reserves();
     }
 
-    #[private]
     pub fn set_total_borrows(&mut self, amount: Balance) -> Balance {
         self.total_borrows = amount;
         return self.get_total_borrows();
     }
+}
 
-    pub fn mint(&mut self, account_id: &AccountId, amount: WBalance) {
-        if !self.token.accounts.contains_key(&account_id.clone()) {
-            self.token.internal_register_account(&account_id.clone());
+#[near_bindgen]
+impl Contract {
+
+    pub fn get_total_borrows(&self) -> Balance {
+        return self.total_borrows;
+    }
+
+    pub fn get_total_reserves(&self) -> Balance {
+        return self.total_reserves;
+    }
+
+    pub fn get_market_info(&self) -> MarketInfo {
+        MarketInfo {
+            underlying_token_id: self.get_underlying_contract_address(),
+            tracked_cash: U128(self.total_cash),
+            total_borrows: U128(self.total_borrows),
+            total_reserves: U128(self.total_reserves),
+            total_supply: U128(self.token.total_supply),
+            exchange_rate: U128(self.get_exchange_rate(U128(self.total_cash))),
+            utilization: U128(self.get_utilization_rate()),
+            borrow_apy: U128(self.get_borrow_rate()),
+            supply_apy: U128(self.get_supply_rate()),
+            accrual_block_number: self.accrual_block_number,
         }
-        self.token.internal_deposit(&account_id, amount.into());
     }
 
-    pub fn burn(&mut self, account_id: &AccountId, amount: WBalance) {
-        if !self.token.accounts.contains_key(&account_id.clone()) {
-            panic!("User with account {} wasn't found", account_id.clone().to_string());
+    pub fn get_account_info(&self, account: AccountId) -> AccountInfo {
+        let dtoken_balance: Balance = self.token.accounts.get(&account).unwrap_or(0);
+        let exchange_rate: Balance = self.get_exchange_rate(U128(self.total_cash));
+
+        AccountInfo {
+            dtoken_balance: U128(dtoken_balance),
+            underlying_balance: U128(if exchange_rate == 0 { 0 } else { dtoken_balance / exchange_rate }),
+            borrow_balance: U128(self.get_borrows_by_account(account.clone())),
+            last_action_block: self.actions.get(&account),
+            borrow_index: U128(self.get_account_borrow_index(&account)),
         }
-        self.token.internal_withdraw(&account_id, amount.into());
+    }
+
+    pub fn get_same_block_guard(&self) -> bool {
+        return self.same_block_guard;
+    }
+
+    /// Refuses borrows and withdrawals in the same block as another action of the account, applied by controller governance after the timelock
+    pub fn set_same_block_guard(&mut self, enabled: bool) {
+        self.assert_controller();
+
+        events::emit::config_change("same_block_guard", &enabled);
+        self.same_block_guard = enabled;
     }
 }
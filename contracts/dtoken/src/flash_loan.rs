@@ -0,0 +1,215 @@
+use crate::*;
+
+/// Flash loan spans a few blocks of cross-contract calls, the lock is released by the resolve callback
+/// and expires after this number of blocks in case the callback failed
+pub const FLASH_LOAN_LOCK_BLOCKS: BlockHeight = 100;
+
+impl Contract {
+    pub fn assert_no_flash_loan(&self) {
+        assert!(
+            self.flash_loan_lock
+                .map_or(true, |lock_block| env::block_height() >= lock_block + FLASH_LOAN_LOCK_BLOCKS),
+            "Action is not allowed while flash loan is in progress"
+        );
+    }
+
+    /// Releases the lock taken at the given block, a lock of another flash loan is kept
+    fn release_flash_loan_lock(&mut self, lock_block: BlockHeight) {
+        if self.flash_loan_lock == Some(lock_block) {
+            self.flash_loan_lock = None;
+        }
+    }
+
+    pub fn get_flash_loan_fee_amount(&self, amount: Balance) -> Balance {
+        amount * self.flash_loan_fee / RATIO_DECIMALS
+    }
+
+    fn get_balance_of_result(&self) -> Balance {
+        match env::promise_result(0) {
+            PromiseResult::NotReady => 0,
+            PromiseResult::Failed => 0,
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<U128>(&result)
+                .unwrap()
+                .into(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Lends underlying tokens to the approved receiver contract and calls its on_flash_loan hook.
+    /// Principal and fee have to be transferred back within the same transaction, otherwise the transaction fails.
+    /// Receiver contracts are approved by governance as tokens which aren't returned can't be taken back.
+    pub fn flash_loan(&mut self, amount: WBalance, receiver: AccountId, msg: String) -> Promise {
+        let initiator = env::signer_account_id();
+        self.assert_registered(&initiator);
+        self.assert_no_flash_loan();
+        self.assert_cash_synced();
+        assert!(
+            self.flash_loan_receivers.contains(&receiver),
+            "Flash loan receiver {} isn't approved by governance",
+            receiver
+        );
+        assert!(Balance::from(amount) > 0, "Amount should be a positive number");
+        assert!(
+            Balance::from(amount) <= self.total_cash,
+            "Not enough liquidity for flash loan of {} tokens",
+            Balance::from(amount)
+        );
+
+        self.accrue_interest();
+        self.record_action(&initiator, true);
+        let lock_block = env::block_height();
+        self.flash_loan_lock = Some(lock_block);
+
+        underlying_token::ft_balance_of(
+            self.get_contract_address(),
+            self.get_underlying_contract_address(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        )
+        .then(ext_self::flash_loan_transfer_callback(
+            amount,
+            receiver,
+            msg,
+            lock_block,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(180),
+        ))
+    }
+
+    #[private]
+    pub fn flash_loan_transfer_callback(
+        &mut self,
+        amount: WBalance,
+        receiver: AccountId,
+        msg: String,
+        lock_block: BlockHeight,
+    ) -> PromiseOrValue<bool> {
+        if !is_promise_success() {
+            log!("Failed to get {} balance on {}, flash loan is cancelled", self.get_contract_address(), self.get_underlying_contract_address());
+            self.release_flash_loan_lock(lock_block);
+            return PromiseOrValue::Value(false);
+        }
+        // Balance before lending includes the principal
+        let balance_before: Balance = self.get_balance_of_result();
+        let fee = self.get_flash_loan_fee_amount(amount.into());
+        self.total_cash = self.total_cash.saturating_sub(Balance::from(amount));
+
+        underlying_token::ft_transfer(
+            receiver.clone(),
+            amount,
+            Some(format!("Flash loan with token_amount {}", Balance::from(amount))),
+            self.get_underlying_contract_address(),
+            ONE_YOCTO,
+            self.terra_gas(20),
+        )
+        .then(ext_flash_loan_receiver::on_flash_loan(
+            env::signer_account_id(),
+            amount,
+            U128(fee),
+            msg,
+            receiver.clone(),
+            NO_DEPOSIT,
+            self.terra_gas(60),
+        ))
+        .then(underlying_token::ft_balance_of(
+            self.get_contract_address(),
+            self.get_underlying_contract_address(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        ))
+        .then(ext_self::flash_loan_resolve_callback(
+            receiver,
+            amount,
+            U128(balance_before),
+            lock_block,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(40),
+        )).into()
+    }
+
+    /// Compares underlying balance with the one before lending and credits the fee to reserves.
+    /// Returned tokens are accounted and the lock is released before an unpaid loan fails the transaction,
+    /// so the failure doesn't roll them back.
+    #[private]
+    pub fn flash_loan_resolve_callback(
+        &mut self,
+        receiver: AccountId,
+        amount: WBalance,
+        balance_before: WBalance,
+        lock_block: BlockHeight,
+    ) -> PromiseOrValue<bool> {
+        let initiator = env::signer_account_id();
+        let amount: Balance = amount.into();
+        let fee = self.get_flash_loan_fee_amount(amount);
+        self.release_flash_loan_lock(lock_block);
+
+        let balance_after: Balance = self.get_balance_of_result();
+        let returned: Balance = (balance_after + amount).saturating_sub(balance_before.into());
+        let repaid = returned >= amount + fee;
+
+        if repaid {
+            self.total_reserves += fee;
+            self.total_cash += amount + fee;
+        } else {
+            self.total_cash += returned;
+        }
+        events::emit::flash_loan(&initiator, &receiver, amount, if repaid { fee } else { 0 }, repaid);
+
+        if repaid {
+            return PromiseOrValue::Value(true);
+        }
+        ext_self::flash_loan_not_repaid(
+            receiver,
+            U128(amount + fee - returned),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(5),
+        ).into()
+    }
+
+    #[private]
+    pub fn flash_loan_not_repaid(&mut self, receiver: AccountId, shortfall: WBalance) {
+        env::panic_str(&format!(
+            "Flash loan wasn't repaid by {}, {} tokens are missing",
+            receiver,
+            Balance::from(shortfall)
+        ));
+    }
+
+    pub fn get_flash_loan_receivers(&self) -> Vec<AccountId> {
+        self.flash_loan_receivers.to_vec()
+    }
+
+    /// Approves the receiver contract for flash loans, applied by controller governance after the timelock
+    pub fn add_flash_loan_receiver(&mut self, receiver: AccountId) {
+        self.assert_controller();
+
+        events::emit::config_change("add_flash_loan_receiver", &receiver);
+        self.flash_loan_receivers.insert(&receiver);
+    }
+
+    /// Revokes the flash loan receiver approval, applied by controller governance after the timelock
+    pub fn remove_flash_loan_receiver(&mut self, receiver: AccountId) {
+        self.assert_controller();
+
+        events::emit::config_change("remove_flash_loan_receiver", &receiver);
+        self.flash_loan_receivers.remove(&receiver);
+    }
+
+    pub fn get_flash_loan_fee(&self) -> WRatio {
+        U128(self.flash_loan_fee)
+    }
+
+    /// Sets the fee charged on flash loans, applied by controller governance after the timelock
+    pub fn set_flash_loan_fee(&mut self, fee: WRatio) {
+        self.assert_controller();
+        assert!(Ratio::from(fee) <= RATIO_DECIMALS, "Flash loan fee should be less or equal to 100%");
+
+        events::emit::config_change("flash_loan_fee", &fee);
+        self.flash_loan_fee = fee.into();
+    }
+}
@@ -45,6 +45,20 @@ impl FungibleTokenReceiver for Contract {
         match action {
             "SUPPLY" => self.supply(amount),
             "REPAY" => self.repay(amount),
+            "LIQUIDATION" => {
+                let memo_data = &msg["memo"];
+                let borrower: AccountId = memo_data["borrower"]
+                    .as_str()
+                    .expect("Borrower is required for liquidation")
+                    .parse()
+                    .expect("Borrower should be a valid account ID");
+                let collateral_dtoken: AccountId = memo_data["collateral_dtoken"]
+                    .as_str()
+                    .expect("Collateral dtoken is required for liquidation")
+                    .parse()
+                    .expect("Collateral dtoken should be a valid account ID");
+                self.liquidation(amount, borrower, collateral_dtoken)
+            }
             _ => {
                 log!("Incorrect command in transfer: {}", action);
                 PromiseOrValue::Value(amount)
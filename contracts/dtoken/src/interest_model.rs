@@ -1,14 +1,80 @@
 use crate::*;
 
+pub use math::{BLOCKS_PER_YEAR, INITIAL_BORROW_INDEX};
+
+pub use general::InterestRateModel;
+
+impl Contract {
+    pub fn get_utilization_rate(&self) -> Ratio {
+        math::utilization_rate(self.total_cash, self.total_borrows, self.total_reserves)
+    }
+
+    pub fn accrue_interest(&mut self) {
+        let current_block = env::block_height();
+        let blocks = Balance::from(current_block - self.accrual_block_number);
+        if blocks == 0 {
+            return;
+        }
+
+        let accrual = math::accrue_interest(
+            self.total_borrows,
+            self.borrow_index,
+            self.get_borrow_rate(),
+            self.interest_rate_model.reserve_factor.into(),
+            blocks,
+        );
+
+        self.total_borrows += accrual.interest_accumulated;
+        self.total_reserves += accrual.reserves_accumulated;
+        let index_changed = accrual.borrow_index != self.borrow_index;
+        self.borrow_index = accrual.borrow_index;
+        self.accrual_block_number = current_block;
+
+        if index_changed {
+            events::emit::accrue_interest(
+                accrual.interest_accumulated,
+                self.borrow_index,
+                self.total_borrows,
+                self.total_reserves,
+            );
+        }
+    }
+}
+
 #[near_bindgen]
 impl Contract {
-    pub fn get_borrow_rate(&self) -> Balance {
-        1
+    /// Annual borrow rate, RATIO_DECIMALS is 100%
+    pub fn get_borrow_rate(&self) -> Ratio {
+        let model = &self.interest_rate_model;
+        math::borrow_rate(
+            self.get_utilization_rate(),
+            model.base_rate.into(),
+            model.multiplier.into(),
+            model.kink.into(),
+            model.jump_multiplier.into(),
+        )
     }
 
-    pub fn get_supply_rate(&self) -> Balance {
-        1
+    /// Annual supply rate, RATIO_DECIMALS is 100%
+    pub fn get_supply_rate(&self) -> Ratio {
+        math::supply_rate(
+            self.get_utilization_rate(),
+            self.get_borrow_rate(),
+            self.interest_rate_model.reserve_factor.into(),
+        )
     }
 
-    
-}
\ No newline at end of file
+    pub fn get_interest_rate_model(&self) -> InterestRateModel {
+        self.interest_rate_model.clone()
+    }
+
+    /// Sets the borrow rate model, applied by controller governance after the timelock
+    pub fn set_interest_rate_model(&mut self, model: InterestRateModel) {
+        self.assert_controller();
+        model.assert_valid();
+
+        self.accrue_interest();
+        events::emit::config_change("interest_rate_model", &model);
+        self.interest_rate_model = model;
+    }
+}
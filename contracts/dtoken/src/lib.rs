@@ -6,6 +6,13 @@ mod repay;
 mod supply;
 mod withdraw;
 mod interest_model;
+mod token;
+mod storage;
+mod flash_loan;
+mod native;
+mod bad_debt;
+mod liquidation;
+mod upgrade;
 
 pub use crate::borrow::*;
 pub use crate::common::*;
@@ -15,14 +22,22 @@ pub use crate::repay::*;
 pub use crate::supply::*;
 pub use crate::withdraw::*;
 pub use crate::interest_model::*;
+pub use crate::token::*;
+pub use crate::storage::*;
+pub use crate::flash_loan::*;
+pub use crate::native::*;
+pub use crate::bad_debt::*;
+pub use crate::liquidation::*;
+pub use crate::upgrade::*;
 
 
 #[allow(unused_imports)]
 use general::*;
 
 use near_contract_standards::fungible_token::FungibleToken;
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet};
 use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{env, ext_contract, is_promise_success, log, near_bindgen, AccountId, Balance, BorshStorageKey, Gas, Promise, PromiseOrValue, PromiseResult, BlockHeight};
@@ -33,7 +48,12 @@ pub type TokenAmount = u128;
 enum StorageKeys {
     Borrows,
     Config,
-    Actions
+    Actions,
+    BorrowIndexes,
+    Metadata,
+    StateVersion,
+    FlashLoanReceivers,
+    StorageDeposits,
 }
 
 #[near_bindgen]
@@ -61,7 +81,46 @@ pub struct Contract {
     config: LazyOption<Config>,
 
     /// BlockHeight of last action user produced
-    actions: LookupMap<AccountId, BlockHeight>
+    actions: LookupMap<AccountId, BlockHeight>,
+
+    /// Amount of underlying tokens held by the contract which aren't borrowed
+    total_cash: TokenAmount,
+
+    /// Whether total_cash is known, it's false from migration of the released state until the underlying balance is read
+    is_cash_synced: bool,
+
+    /// Interest rate model parameters
+    interest_rate_model: InterestRateModel,
+
+    /// Accumulator of interest rates applied to borrows since market creation
+    borrow_index: u128,
+
+    /// BlockHeight of last interest accrual
+    accrual_block_number: BlockHeight,
+
+    /// Account Id -> Borrow index at the moment of last borrow balance change
+    borrow_indexes: LookupMap<AccountId, u128>,
+
+    /// Refuse borrows and withdrawals in the same block as another action of the account
+    same_block_guard: bool,
+
+    /// Dtoken metadata
+    metadata: LazyOption<FungibleTokenMetadata>,
+
+    /// Fee charged for flash loans and credited to reserves, RATIO_DECIMALS is 100%
+    flash_loan_fee: Ratio,
+
+    /// BlockHeight at which the flash loan in progress was started
+    flash_loan_lock: Option<BlockHeight>,
+
+    /// Contracts approved by governance to receive flash loans
+    flash_loan_receivers: UnorderedSet<AccountId>,
+
+    /// Whether underlying token is wNEAR which is wrapped and unwrapped for native NEAR flows
+    is_native_market: bool,
+
+    /// Account Id -> Storage deposit paid on registration, accounts registered by the released contract paid nothing
+    storage_deposits: LookupMap<AccountId, Balance>,
 }
 
 impl Default for Contract {
@@ -84,15 +143,33 @@ trait UnderlineTokenInterface {
     fn ft_resolve_transfer(&self, account_id: AccountId) -> U128;
 }
 
+#[ext_contract(wnear)]
+trait WrappedNearInterface {
+    fn near_deposit(&mut self);
+    fn near_withdraw(&mut self, amount: WBalance);
+}
+
 #[ext_contract(controller)]
 trait ControllerInterface {
     fn increase_supplies(&mut self, account: AccountId, token_address: AccountId, token_amount: WBalance);
     fn decrease_supplies(&mut self, account_id: AccountId, amount: WBalance);
-    fn repay_borrows(&mut self, account_id: AccountId, token_address: AccountId, token_amount: WBalance);
+    fn repay_borrows(&mut self, account_id: AccountId, token_address: AccountId, token_amount: WBalance, borrow_balance: WBalance);
     fn withdraw_supplies(&mut self, account_id: AccountId, token_address: AccountId, token_amount: WBalance) -> Promise;
-    fn make_borrow(&mut self, account_id: AccountId, token_address: AccountId, token_amount: WBalance); 
+    fn make_borrow(&mut self, account_id: AccountId, token_address: AccountId, token_amount: WBalance, borrow_balance: WBalance);
     fn decrease_borrows(&mut self, account: AccountId, token_address: AccountId, token_amount: WBalance); 
+    fn sync_borrows(&mut self, account: AccountId, token_address: AccountId, borrow_balance: WBalance);
+    fn transfer_supplies(&mut self, sender_id: AccountId, receiver_id: AccountId, token_address: AccountId, token_amount: WBalance, check_health: bool);
+    fn liquidation(&mut self, borrower: AccountId, borrowing_market: AccountId, collateral_market: AccountId, liquidator: AccountId, liquidation_amount: WBalance, borrow_amount: WBalance) -> LiquidationResult;
+}
 
+#[ext_contract(ext_ft_receiver)]
+trait DtokenReceiverInterface {
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: WBalance, msg: String) -> PromiseOrValue<U128>;
+}
+
+#[ext_contract(ext_flash_loan_receiver)]
+trait FlashLoanReceiverInterface {
+    fn on_flash_loan(&mut self, initiator_id: AccountId, amount: WBalance, fee: WBalance, msg: String);
 }
 
 #[ext_contract(ext_self)]
@@ -109,6 +186,26 @@ trait InternalTokenInterface {
     fn withdraw_balance_of_callback(&mut self, dtoken_amount: Balance);
     fn withdraw_supplies_callback(&mut self, user_account: AccountId, token_amount: WBalance, dtoken_amount: WBalance);
     fn withdraw_ft_transfer_call_callback(&mut self, token_amount: WBalance, dtoken_amount: WBalance);
+
+    fn ft_transfer_balance_of_callback(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: WBalance, memo: Option<String>, msg: Option<String>) -> PromiseOrValue<U128>;
+    fn ft_transfer_refused(&mut self, amount: WBalance);
+    fn ft_transfer_controller_callback(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: WBalance, memo: Option<String>);
+    fn ft_transfer_call_controller_callback(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: WBalance, memo: Option<String>, msg: String) -> PromiseOrValue<U128>;
+    fn ft_resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: WBalance) -> U128;
+    fn ft_refund_balance_of_callback(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: WBalance);
+    fn ft_refund_controller_callback(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: WBalance);
+
+    fn flash_loan_transfer_callback(&mut self, amount: WBalance, receiver: AccountId, msg: String, lock_block: BlockHeight) -> PromiseOrValue<bool>;
+    fn flash_loan_resolve_callback(&mut self, receiver: AccountId, amount: WBalance, balance_before: WBalance, lock_block: BlockHeight) -> PromiseOrValue<bool>;
+    fn flash_loan_not_repaid(&mut self, receiver: AccountId, shortfall: WBalance);
+
+    fn supply_native_callback(&mut self, amount: WBalance) -> PromiseOrValue<U128>;
+    fn supply_native_resolve_callback(&mut self, amount: WBalance) -> U128;
+    fn unwrap_near_callback(&mut self, receiver: AccountId, amount: WBalance) -> Promise;
+
+    fn liquidation_callback(&mut self, borrower: AccountId, amount: WBalance) -> U128;
+
+    fn migrate_total_cash_callback(&mut self);
 }
 
 #[near_bindgen]
@@ -116,7 +213,16 @@ impl Contract {
     /// Initializes the contract with the given config. Needs to be called once.
     #[init]
     pub fn new(config: Config) -> Self {
-        Self {
+        Self::new_with_metadata(config, Self::default_metadata())
+    }
+
+    /// Initializes the contract with the given config and dtoken metadata. Needs to be called once.
+    #[init]
+    pub fn new_with_metadata(config: Config, metadata: FungibleTokenMetadata) -> Self {
+        metadata.assert_valid();
+        events::emit::config_change("config", &config);
+        Self::write_state_version(STATE_VERSION);
+        let mut this = Self {
             initial_exchange_rate: u128::from(config.initial_exchange_rate.clone()),
             total_reserves: 0,
             total_borrows: 0,
@@ -125,6 +231,22 @@ impl Contract {
             token: FungibleToken::new(b"t".to_vec()),
             config: LazyOption::new(StorageKeys::Config, Some(&config)),
             actions: LookupMap::new(StorageKeys::Actions),
-        }
+            total_cash: 0,
+            is_cash_synced: true,
+            interest_rate_model: InterestRateModel::default(),
+            borrow_index: INITIAL_BORROW_INDEX,
+            accrual_block_number: env::block_height(),
+            borrow_indexes: LookupMap::new(StorageKeys::BorrowIndexes),
+            same_block_guard: false,
+            metadata: LazyOption::new(StorageKeys::Metadata, Some(&metadata)),
+            flash_loan_fee: 0,
+            flash_loan_lock: None,
+            flash_loan_receivers: UnorderedSet::new(StorageKeys::FlashLoanReceivers),
+            is_native_market: false,
+            storage_deposits: LookupMap::new(StorageKeys::StorageDeposits),
+        };
+        // Contract account holds dtokens while transfers are being approved by controller
+        this.token.internal_register_account(&env::current_account_id());
+        this
     }
 }
@@ -0,0 +1,104 @@
+use crate::*;
+
+impl Contract {
+    /// Repays borrow of the unhealthy account with underlying tokens received in ft_on_transfer,
+    /// collateral of the borrower is moved to the liquidator by the collateral market
+    pub fn liquidation(&mut self, amount: WBalance, borrower: AccountId, collateral_market: AccountId) -> PromiseOrValue<U128> {
+        let liquidator = env::signer_account_id();
+        self.assert_no_flash_loan();
+        self.assert_cash_synced();
+        self.accrue_interest();
+        self.record_action(&liquidator, false);
+
+        let borrow_amount = self.get_borrows_by_account(borrower.clone());
+        assert!(borrow_amount > 0, "Account {} has no borrows", borrower);
+
+        controller::liquidation(
+            borrower.clone(),
+            self.get_contract_address(),
+            collateral_market,
+            liquidator,
+            amount,
+            U128(borrow_amount),
+            self.get_controller_address(),
+            NO_DEPOSIT,
+            self.terra_gas(80),
+        )
+        .then(ext_self::liquidation_callback(
+            borrower,
+            amount,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(30),
+        ))
+        .into()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Decreases the borrow by the amount approved by controller, the rest is refunded to the liquidator
+    #[private]
+    pub fn liquidation_callback(&mut self, borrower: AccountId, amount: WBalance) -> U128 {
+        let result: LiquidationResult = match env::promise_result(0) {
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<LiquidationResult>(&result).unwrap(),
+            _ => {
+                log!("Liquidation of {} by {} was refused by controller", borrower, env::signer_account_id());
+                LiquidationResult::default()
+            }
+        };
+
+        // Borrow could be repaid while liquidation was in progress, controller has already decreased it
+        // by the approved amount, so it's synced with the borrow left on the market
+        let approved_amount = Balance::from(result.repaid_amount);
+        let repaid_amount = std::cmp::min(approved_amount, self.get_borrows_by_account(borrower.clone()));
+        if repaid_amount > 0 {
+            self.decrease_borrows(borrower.clone(), U128(repaid_amount));
+            self.total_cash += repaid_amount;
+        }
+        if repaid_amount < approved_amount {
+            controller::sync_borrows(
+                borrower.clone(),
+                self.get_contract_address(),
+                U128(self.get_borrows_by_account(borrower)),
+                self.get_controller_address(),
+                NO_DEPOSIT,
+                self.terra_gas(10),
+            );
+        }
+        U128(Balance::from(amount) - repaid_amount)
+    }
+
+    /// Moves dtokens of the borrower worth seized_amount of underlying tokens to the liquidator,
+    /// dtokens worth protocol_fee are burned and credited to reserves. Called by controller only.
+    pub fn seize(&mut self, borrower: AccountId, liquidator: AccountId, seized_amount: WBalance, protocol_fee: WBalance) {
+        self.assert_controller();
+        self.assert_no_flash_loan();
+        self.assert_cash_synced();
+        self.assert_registered(&liquidator);
+        self.accrue_interest();
+
+        let exchange_rate: Balance = self.get_exchange_rate(U128(self.total_cash));
+        let seized_dtokens: Balance = Balance::from(seized_amount) * exchange_rate;
+        let fee_dtokens: Balance = Balance::from(protocol_fee) * exchange_rate;
+        assert!(
+            self.token.internal_unwrap_balance_of(&borrower) >= seized_dtokens,
+            "Not enough dtokens of {} to seize {} tokens",
+            borrower,
+            Balance::from(seized_amount)
+        );
+
+        if seized_dtokens > fee_dtokens {
+            self.token.internal_transfer(
+                &borrower,
+                &liquidator,
+                seized_dtokens - fee_dtokens,
+                Some(format!("Liquidation with token_amount {}", Balance::from(seized_amount))),
+            );
+        }
+        if fee_dtokens > 0 {
+            self.burn(&borrower, U128(fee_dtokens));
+            self.total_reserves += Balance::from(protocol_fee);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::*;
+
+impl Contract {
+    pub fn assert_native_market(&self) {
+        assert!(self.is_native_market, "Market {} doesn't support native NEAR", self.get_contract_address());
+    }
+
+    /// Sends underlying tokens to the receiver, native markets unwrap them and send NEAR instead
+    pub fn transfer_underlying(&self, receiver: AccountId, amount: WBalance, memo: String, gas: Gas) -> Promise {
+        if self.is_native_market {
+            return wnear::near_withdraw(
+                amount,
+                self.get_underlying_contract_address(),
+                ONE_YOCTO,
+                self.terra_gas(10),
+            )
+            .then(ext_self::unwrap_near_callback(
+                receiver,
+                amount,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                self.terra_gas(10),
+            ));
+        }
+
+        underlying_token::ft_transfer(
+            receiver,
+            amount,
+            Some(memo),
+            self.get_underlying_contract_address(),
+            ONE_YOCTO,
+            gas,
+        )
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Wraps attached NEAR through the underlying wNEAR contract and supplies it.
+    /// Dtoken account has to be registered on the wNEAR contract.
+    #[payable]
+    pub fn supply_native(&mut self) -> Promise {
+        self.assert_native_market();
+        self.assert_registered(&env::signer_account_id());
+        self.assert_no_flash_loan();
+        self.assert_cash_synced();
+        let amount: Balance = env::attached_deposit();
+        assert!(amount > 0, "Attached deposit should be a positive number");
+
+        wnear::near_deposit(
+            self.get_underlying_contract_address(),
+            amount,
+            self.terra_gas(10),
+        )
+        .then(ext_self::supply_native_callback(
+            U128(amount),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(150),
+        ))
+        .then(ext_self::supply_native_resolve_callback(
+            U128(amount),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            self.terra_gas(40),
+        ))
+    }
+
+    #[private]
+    pub fn supply_native_callback(&mut self, amount: WBalance) -> PromiseOrValue<U128> {
+        if !is_promise_success() {
+            // Deposit of the failed wrapping is refunded to the contract
+            log!("Failed to wrap {} NEAR for {}", Balance::from(amount), env::signer_account_id());
+            Promise::new(env::signer_account_id()).transfer(amount.into());
+            return PromiseOrValue::Value(U128(0));
+        }
+        self.supply(amount)
+    }
+
+    /// Unwraps and sends back the part of supply which wasn't used
+    #[private]
+    pub fn supply_native_resolve_callback(&mut self, amount: WBalance) -> U128 {
+        let unused_amount: Balance = match env::promise_result(0) {
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<U128>(&result)
+                .unwrap()
+                .into(),
+            _ => {
+                log!("Supply of {} wrapped NEAR for {} has failed", Balance::from(amount), env::signer_account_id());
+                0
+            }
+        };
+
+        if unused_amount > 0 {
+            self.transfer_underlying(
+                env::signer_account_id(),
+                U128(unused_amount),
+                format!("Refund with token_amount {}", unused_amount),
+                self.terra_gas(10),
+            );
+        }
+        U128(unused_amount)
+    }
+
+    #[private]
+    pub fn unwrap_near_callback(&mut self, receiver: AccountId, amount: WBalance) -> Promise {
+        assert!(
+            is_promise_success(),
+            "Failed to unwrap {} NEAR for {}",
+            Balance::from(amount),
+            receiver
+        );
+        Promise::new(receiver).transfer(amount.into())
+    }
+
+    pub fn is_native_market(&self) -> bool {
+        self.is_native_market
+    }
+
+    /// Marks the wNEAR market which wraps and unwraps native NEAR, applied by controller governance after the timelock
+    pub fn set_native_market(&mut self, enabled: bool) {
+        self.assert_controller();
+
+        events::emit::config_change("native_market", &enabled);
+        self.is_native_market = enabled;
+    }
+}
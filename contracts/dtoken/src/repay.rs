@@ -1,15 +1,19 @@
 use crate::*;
 
-#[near_bindgen]
 impl Contract {
-
+    /// Repays borrows of the account with underlying tokens received in ft_on_transfer
     pub fn repay(&mut self, token_amount: WBalance) -> PromiseOrValue<U128> {
+        self.assert_no_flash_loan();
+        self.assert_cash_synced();
+        self.accrue_interest();
+        self.record_action(&env::signer_account_id(), false);
         let debt_amount = self.get_borrows_by_account(env::signer_account_id());
-        assert!(Balance::from(token_amount) >= debt_amount*self.get_borrow_rate(), "repay amount {} is less than existing borrow {}", Balance::from(token_amount), debt_amount * self.get_borrow_rate());
+        assert!(Balance::from(token_amount) >= debt_amount, "repay amount {} is less than existing borrow {}", Balance::from(token_amount), debt_amount);
         return controller::repay_borrows(
             env::signer_account_id(),
             self.get_contract_address(),
             U128(debt_amount),
+            U128(debt_amount),
             self.get_controller_address(),
             NO_DEPOSIT,
             self.terra_gas(10),
@@ -21,14 +25,23 @@ impl Contract {
             self.terra_gas(10),
         )).into();
     }
+}
 
+#[near_bindgen]
+impl Contract {
+    #[private]
     pub fn controller_repay_borrows_callback(&mut self, amount: WBalance) -> PromiseOrValue<U128> {
         if !is_promise_success() {
             log!("failed to update user {} balance {}: user is not registered", env::signer_account_id(), Balance::from(amount));
             return PromiseOrValue::Value(amount);
         } 
-        let extra_balance = Balance::from(amount) - self.get_borrows_by_account(env::signer_account_id());
-        self.decrease_borrows(env::signer_account_id(), U128(self.get_borrows_by_account(env::signer_account_id())));
+        // Interest may have been accrued by other actions since the repayment was approved
+        let borrow_amount = self.get_borrows_by_account(env::signer_account_id());
+        let repaid_amount = std::cmp::min(Balance::from(amount), borrow_amount);
+        let extra_balance = Balance::from(amount) - repaid_amount;
+        self.decrease_borrows(env::signer_account_id(), U128(repaid_amount));
+        self.total_cash += repaid_amount;
+        events::emit::repay(&env::signer_account_id(), repaid_amount);
         return PromiseOrValue::Value(U128(extra_balance));
         
     }
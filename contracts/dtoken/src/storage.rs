@@ -0,0 +1,114 @@
+use crate::*;
+
+use near_contract_standards::storage_management::{StorageBalance, StorageBalanceBounds, StorageManagement};
+use near_sdk::{assert_one_yocto, StorageUsage};
+
+/// Storage taken by borrows, borrow index, last action and storage deposit of an account on top of the token balance
+const EXTRA_ACCOUNT_STORAGE: StorageUsage = 700;
+
+impl Contract {
+    pub fn assert_registered(&self, account_id: &AccountId) {
+        assert!(
+            self.token.accounts.contains_key(account_id),
+            "Account {} isn't registered, storage_deposit is required",
+            account_id
+        );
+    }
+
+    fn storage_min_balance(&self) -> Balance {
+        Balance::from(self.token.account_storage_usage + EXTRA_ACCOUNT_STORAGE) * env::storage_byte_cost()
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    /// Storage balance is fixed, so registration_only doesn't change the charged amount
+    #[allow(unused_variables)]
+    #[payable]
+    fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>) -> StorageBalance {
+        let amount: Balance = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+
+        if self.token.accounts.contains_key(&account_id) {
+            log!("The account is already registered, refunding the deposit");
+            if amount > 0 {
+                Promise::new(env::predecessor_account_id()).transfer(amount);
+            }
+        } else {
+            let min_balance = self.storage_min_balance();
+            assert!(amount >= min_balance, "The attached deposit is less than the minimum storage balance");
+
+            self.token.internal_register_account(&account_id);
+            self.storage_deposits.insert(&account_id, &min_balance);
+            let refund = amount - min_balance;
+            if refund > 0 {
+                Promise::new(env::predecessor_account_id()).transfer(refund);
+            }
+        }
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Storage balance is fixed, so there is never anything available to withdraw
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        self.assert_registered(&account_id);
+
+        if let Some(amount) = amount {
+            assert_eq!(Balance::from(amount), 0, "The amount is greater than the available storage balance");
+        }
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Releases account storage once it has no dtokens and no borrows, the deposit paid on registration is refunded.
+    /// Force isn't supported as burning dtokens would leave supplies on controller.
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        assert_ne!(force, Some(true), "Force unregister isn't supported, withdraw and repay first");
+        let account_id = env::predecessor_account_id();
+
+        let balance = match self.token.accounts.get(&account_id) {
+            Some(balance) => balance,
+            None => {
+                log!("The account {} is not registered", account_id);
+                return false;
+            }
+        };
+        assert_eq!(balance, 0, "Can't unregister the account with the positive dtoken balance, withdraw first");
+        assert_eq!(
+            self.get_borrows_by_account(account_id.clone()),
+            0,
+            "Can't unregister the account with existing borrows, repay first"
+        );
+
+        self.token.accounts.remove(&account_id);
+        self.borrows.remove(&account_id);
+        self.borrow_indexes.remove(&account_id);
+        self.actions.remove(&account_id);
+        let deposit = self.storage_deposits.remove(&account_id).unwrap_or(0);
+
+        Promise::new(account_id).transfer(deposit + 1);
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        let min_balance = self.storage_min_balance();
+        StorageBalanceBounds {
+            min: min_balance.into(),
+            max: Some(min_balance.into()),
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        if self.token.accounts.contains_key(&account_id) {
+            Some(StorageBalance {
+                total: self.storage_min_balance().into(),
+                available: 0.into(),
+            })
+        } else {
+            None
+        }
+    }
+}
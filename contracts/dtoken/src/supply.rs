@@ -1,9 +1,13 @@
 use crate::*;
 
-#[near_bindgen]
 impl Contract {
-    #[payable]
+    /// Mints dtokens for underlying tokens received in ft_on_transfer
     pub fn supply(&mut self, token_amount: WBalance) -> PromiseOrValue<U128> {
+        self.assert_registered(&env::signer_account_id());
+        self.assert_no_flash_loan();
+        self.assert_cash_synced();
+        self.accrue_interest();
+        self.record_action(&env::signer_account_id(), false);
         underlying_token::ft_balance_of(
             env::current_account_id(),
             self.get_underlying_contract_address(),
@@ -17,8 +21,11 @@ impl Contract {
             self.terra_gas(60),
         )).into()
     }
- 
-    #[allow(dead_code)]
+}
+
+#[near_bindgen]
+impl Contract {
+    #[private]
     pub fn supply_balance_of_callback(&mut self, token_amount: WBalance) -> PromiseOrValue<U128> {
 
         if !is_promise_success() {
@@ -41,13 +48,6 @@ impl Contract {
             &self.get_signer_address(),
             dtoken_amount.into()
         );
-        log!(
-            "Supply from Account {} to Dtoken contract {} with tokens amount {} was successfully done!",
-            self.get_signer_address(),
-            self.get_contract_address(),
-            Balance::from(token_amount)
-        );
-        
         controller::increase_supplies(
             env::signer_account_id(),
             self.get_contract_address(),
@@ -65,7 +65,7 @@ impl Contract {
         )).into()
     }
 
-    #[allow(dead_code)]
+    #[private]
     pub fn controller_increase_supplies_callback(&mut self, amount: WBalance, dtoken_amount: WBalance) -> PromiseOrValue<U128> {
         if !is_promise_success(){
             log!("failed to increase supply {} balance of {} on controller", env::signer_account_id(), self.get_contract_address());
@@ -75,6 +75,8 @@ impl Contract {
             );
             return PromiseOrValue::Value(amount);
         } 
+        self.total_cash += Balance::from(amount);
+        events::emit::supply(&env::signer_account_id(), amount.into(), dtoken_amount.into());
         PromiseOrValue::Value(U128(0))
     }
 
@@ -0,0 +1,337 @@
+use crate::*;
+
+use near_contract_standards::fungible_token::core::FungibleTokenCore;
+use near_contract_standards::fungible_token::metadata::{FungibleTokenMetadata, FungibleTokenMetadataProvider, FT_METADATA_SPEC};
+use near_contract_standards::fungible_token::resolver::FungibleTokenResolver;
+use near_sdk::assert_one_yocto;
+
+impl Contract {
+    pub fn default_metadata() -> FungibleTokenMetadata {
+        FungibleTokenMetadata {
+            spec: FT_METADATA_SPEC.to_string(),
+            name: "Nearlend dToken".to_string(),
+            symbol: "dTOKEN".to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 24,
+        }
+    }
+
+    /// Converts dtokens to underlying tokens with the exchange rate of the given underlying balance of the market,
+    /// used by withdraw and transfers so dtokens and supplies on controller are converted the same way
+    pub fn get_underlying_amount(&self, dtoken_amount: Balance, underlying_balance: Balance) -> Balance {
+        let exchange_rate: Balance = self.get_exchange_rate(U128(underlying_balance));
+        assert!(exchange_rate > 0, "Exchange rate should be a positive number");
+        return dtoken_amount / exchange_rate;
+    }
+
+    /// Moves dtokens from the sender to the contract account and reads the underlying balance to convert them,
+    /// the transfer is continued by ft_transfer_balance_of_callback
+    fn escrow_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: Option<String>,
+        callback_gas: Gas,
+    ) -> Promise {
+        assert_ne!(sender_id, receiver_id, "Sender and receiver should be different");
+        assert!(Balance::from(amount) > 0, "The amount should be a positive number");
+        self.assert_registered(&receiver_id);
+
+        let contract_id = self.get_contract_address();
+        self.token.internal_transfer(&sender_id, &contract_id, amount.into(), None);
+
+        underlying_token::ft_balance_of(
+            contract_id,
+            self.get_underlying_contract_address(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        )
+        .then(ext_self::ft_transfer_balance_of_callback(
+            sender_id,
+            receiver_id,
+            amount,
+            memo,
+            msg,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            callback_gas,
+        ))
+    }
+
+    fn read_underlying_balance() -> Option<Balance> {
+        match env::promise_result(0) {
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<U128>(&result).ok().map(Balance::from),
+            _ => None,
+        }
+    }
+
+    fn release_transfer(&mut self, receiver_id: &AccountId, amount: Balance, memo: Option<String>) {
+        let contract_id = self.get_contract_address();
+        self.token.internal_transfer(&contract_id, receiver_id, amount, memo);
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenCore for Contract {
+    /// Transfers dtokens along with supplies recorded on controller.
+    /// Controller refuses the transfer if the sender would become liquidatable.
+    #[payable]
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        self.escrow_transfer(sender_id, receiver_id, amount, memo, None, self.terra_gas(50));
+    }
+
+    #[payable]
+    fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        self.escrow_transfer(sender_id, receiver_id, amount, memo, Some(msg), self.terra_gas(150))
+            .into()
+    }
+
+    fn ft_total_supply(&self) -> U128 {
+        self.token.ft_total_supply()
+    }
+
+    fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        self.token.ft_balance_of(account_id)
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenResolver for Contract {
+    /// Takes back dtokens unused by the receiver, refunds to unregistered senders would be burned,
+    /// so such dtokens stay with the receiver along with the supplies
+    #[private]
+    fn ft_resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> U128 {
+        let amount: Balance = amount.into();
+        let unused_amount: Balance = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<U128>(&value)
+                .map(|unused_amount| std::cmp::min(amount, unused_amount.into()))
+                .unwrap_or(amount),
+            _ => amount,
+        };
+
+        let receiver_balance: Balance = self.token.accounts.get(&receiver_id).unwrap_or(0);
+        let refund_amount: Balance = if self.token.accounts.contains_key(&sender_id) {
+            std::cmp::min(unused_amount, receiver_balance)
+        } else {
+            0
+        };
+
+        if refund_amount > 0 {
+            // Refunded dtokens are held by the contract account until their supplies are moved back to the sender
+            let contract_id = self.get_contract_address();
+            self.token.internal_transfer(&receiver_id, &contract_id, refund_amount, None);
+
+            underlying_token::ft_balance_of(
+                contract_id,
+                self.get_underlying_contract_address(),
+                NO_DEPOSIT,
+                self.terra_gas(10),
+            )
+            .then(ext_self::ft_refund_balance_of_callback(
+                sender_id,
+                receiver_id,
+                U128(refund_amount),
+                env::current_account_id().clone(),
+                NO_DEPOSIT,
+                self.terra_gas(25),
+            ));
+        }
+
+        U128(amount - refund_amount)
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenMetadataProvider for Contract {
+    fn ft_metadata(&self) -> FungibleTokenMetadata {
+        self.metadata.get().unwrap()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Moves supplies worth the escrowed dtokens on controller, dtokens which are worth no underlying tokens
+    /// are returned to the sender and the transfer is refused
+    #[private]
+    pub fn ft_transfer_balance_of_callback(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: Option<String>,
+    ) -> PromiseOrValue<U128> {
+        let underlying_balance = match Self::read_underlying_balance() {
+            Some(underlying_balance) => underlying_balance,
+            None => {
+                log!("Failed to get {} balance on {}", self.get_contract_address(), self.get_underlying_contract_address());
+                self.release_transfer(&sender_id, amount.into(), None);
+                return PromiseOrValue::Value(U128(0));
+            }
+        };
+
+        let token_amount = self.get_underlying_amount(amount.into(), underlying_balance);
+        if token_amount == 0 {
+            self.release_transfer(&sender_id, amount.into(), None);
+            return ext_self::ft_transfer_refused(
+                amount,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                self.terra_gas(5),
+            ).into();
+        }
+
+        let transfer = controller::transfer_supplies(
+            sender_id.clone(),
+            receiver_id.clone(),
+            self.get_contract_address(),
+            U128(token_amount),
+            true,
+            self.get_controller_address(),
+            NO_DEPOSIT,
+            self.terra_gas(20),
+        );
+        match msg {
+            Some(msg) => transfer
+                .then(ext_self::ft_transfer_call_controller_callback(
+                    sender_id,
+                    receiver_id,
+                    amount,
+                    memo,
+                    msg,
+                    env::current_account_id().clone(),
+                    NO_DEPOSIT,
+                    self.terra_gas(100),
+                ))
+                .into(),
+            None => transfer
+                .then(ext_self::ft_transfer_controller_callback(
+                    sender_id,
+                    receiver_id,
+                    amount,
+                    memo,
+                    env::current_account_id().clone(),
+                    NO_DEPOSIT,
+                    self.terra_gas(10),
+                ))
+                .into(),
+        }
+    }
+
+    #[private]
+    pub fn ft_transfer_refused(&mut self, amount: U128) {
+        env::panic_str(&format!(
+            "Transfer of {} dtokens is refused, they are worth no underlying tokens",
+            Balance::from(amount)
+        ));
+    }
+
+    #[private]
+    pub fn ft_transfer_controller_callback(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        if !is_promise_success() {
+            log!("Transfer of {} dtokens from {} to {} was refused by controller", Balance::from(amount), sender_id, receiver_id);
+            self.release_transfer(&sender_id, amount.into(), None);
+            return;
+        }
+        self.release_transfer(&receiver_id, amount.into(), memo);
+    }
+
+    #[private]
+    pub fn ft_transfer_call_controller_callback(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        if !is_promise_success() {
+            log!("Transfer of {} dtokens from {} to {} was refused by controller", Balance::from(amount), sender_id, receiver_id);
+            self.release_transfer(&sender_id, amount.into(), None);
+            return PromiseOrValue::Value(U128(0));
+        }
+        self.release_transfer(&receiver_id, amount.into(), memo);
+
+        ext_ft_receiver::ft_on_transfer(
+            sender_id.clone(),
+            amount,
+            msg,
+            receiver_id.clone(),
+            NO_DEPOSIT,
+            self.terra_gas(30),
+        )
+        .then(ext_self::ft_resolve_transfer(
+            sender_id,
+            receiver_id,
+            amount,
+            env::current_account_id().clone(),
+            NO_DEPOSIT,
+            self.terra_gas(50),
+        )).into()
+    }
+
+    /// Moves supplies worth the refunded dtokens back to the sender, dtokens which are worth no underlying tokens
+    /// stay with the receiver
+    #[private]
+    pub fn ft_refund_balance_of_callback(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) {
+        let token_amount = Self::read_underlying_balance()
+            .map(|underlying_balance| self.get_underlying_amount(amount.into(), underlying_balance))
+            .unwrap_or(0);
+        if token_amount == 0 {
+            log!("Refund of {} dtokens from {} to {} can't be converted to supplies", Balance::from(amount), receiver_id, sender_id);
+            self.release_transfer(&receiver_id, amount.into(), None);
+            return;
+        }
+
+        controller::transfer_supplies(
+            receiver_id.clone(),
+            sender_id.clone(),
+            self.get_contract_address(),
+            U128(token_amount),
+            false,
+            self.get_controller_address(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        )
+        .then(ext_self::ft_refund_controller_callback(
+            sender_id,
+            receiver_id,
+            amount,
+            env::current_account_id().clone(),
+            NO_DEPOSIT,
+            self.terra_gas(5),
+        ));
+    }
+
+    /// Releases refunded dtokens to the sender once controller moved their supplies back,
+    /// otherwise dtokens stay with the receiver along with the supplies
+    #[private]
+    pub fn ft_refund_controller_callback(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) {
+        if !is_promise_success() {
+            log!("Refund of {} dtokens from {} to {} was refused by controller", Balance::from(amount), receiver_id, sender_id);
+            self.release_transfer(&receiver_id, amount.into(), None);
+            return;
+        }
+        self.release_transfer(&sender_id, amount.into(), Some("refund".to_string()));
+    }
+}
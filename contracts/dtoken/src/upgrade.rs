@@ -0,0 +1,174 @@
+use crate::*;
+
+use near_sdk::IntoStorageKey;
+
+/// Version of the contract state layout, has to be bumped once per release which changes Contract fields.
+/// Fields added since the released contract ship together as version 1, migrate converts ContractV0 into it.
+pub const STATE_VERSION: u32 = 1;
+
+/// Gas reserved for the upgrade method itself, the rest is attached to the migrate call
+#[cfg(target_arch = "wasm32")]
+const GAS_FOR_UPGRADE: Gas = Gas(20_000_000_000_000);
+
+/// Contract layout of the released contract, written before state versioning
+#[derive(BorshDeserialize)]
+pub struct ContractV0 {
+    initial_exchange_rate: u128,
+    total_reserves: TokenAmount,
+    total_borrows: TokenAmount,
+    borrows: UnorderedMap<AccountId, TokenAmount>,
+    underlying_token: AccountId,
+    token: FungibleToken,
+    config: LazyOption<Config>,
+    actions: LookupMap<AccountId, BlockHeight>,
+}
+
+/// Cash is synced with the underlying balance right after migration, see migrate_total_cash_callback
+impl From<ContractV0> for Contract {
+    fn from(old: ContractV0) -> Self {
+        Self {
+            initial_exchange_rate: old.initial_exchange_rate,
+            total_reserves: old.total_reserves,
+            total_borrows: old.total_borrows,
+            borrows: old.borrows,
+            underlying_token: old.underlying_token,
+            token: old.token,
+            config: old.config,
+            actions: old.actions,
+            total_cash: 0,
+            is_cash_synced: false,
+            interest_rate_model: InterestRateModel::default(),
+            borrow_index: INITIAL_BORROW_INDEX,
+            accrual_block_number: env::block_height(),
+            borrow_indexes: LookupMap::new(StorageKeys::BorrowIndexes),
+            same_block_guard: false,
+            metadata: LazyOption::new(StorageKeys::Metadata, Some(&Self::default_metadata())),
+            flash_loan_fee: 0,
+            flash_loan_lock: None,
+            flash_loan_receivers: UnorderedSet::new(StorageKeys::FlashLoanReceivers),
+            is_native_market: false,
+            storage_deposits: LookupMap::new(StorageKeys::StorageDeposits),
+        }
+    }
+}
+
+impl Contract {
+    /// Refuses actions which depend on the exchange rate or rates while total_cash isn't synced after migration
+    pub fn assert_cash_synced(&self) {
+        assert!(self.is_cash_synced, "Action is not allowed until cash is synced with the underlying balance");
+    }
+
+    /// Reads the underlying balance of the market, it's set as total_cash by migrate_total_cash_callback
+    fn request_total_cash_sync(underlying_token: AccountId) -> Promise {
+        underlying_token::ft_balance_of(
+            env::current_account_id(),
+            underlying_token,
+            NO_DEPOSIT,
+            TGAS * 10,
+        )
+        .then(ext_self::migrate_total_cash_callback(
+            env::current_account_id(),
+            NO_DEPOSIT,
+            TGAS * 10,
+        ))
+    }
+
+    /// Returns the version of stored state, 0 stands for the state written before versioning
+    pub fn read_state_version() -> u32 {
+        env::storage_read(&StorageKeys::StateVersion.into_storage_key())
+            .map(|bytes| u32::try_from_slice(&bytes).expect("State version is corrupted"))
+            .unwrap_or(0)
+    }
+
+    pub fn write_state_version(version: u32) {
+        env::storage_write(&StorageKeys::StateVersion.into_storage_key(), &version.try_to_vec().unwrap());
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_state_version(&self) -> u32 {
+        Self::read_state_version()
+    }
+
+    /// Converts the state written by a previous version of the contract into the current layout.
+    /// Called by upgrade right after the new code has been deployed.
+    #[init(ignore_state)]
+    #[private]
+    pub fn migrate() -> Self {
+        let version = Self::read_state_version();
+        assert!(
+            version <= STATE_VERSION,
+            "Can't migrate from state version {} to older version {}",
+            version,
+            STATE_VERSION
+        );
+
+        let contract: Contract = match version {
+            0 => {
+                let old: ContractV0 = env::state_read().expect("Contract is not initialized");
+                // Released contract computed the exchange rate from the underlying balance instead of cash
+                Self::request_total_cash_sync(old.underlying_token.clone());
+
+                let mut contract: Contract = old.into();
+                // Contract account holds dtokens while transfers are being approved by controller
+                let contract_id = env::current_account_id();
+                if !contract.token.accounts.contains_key(&contract_id) {
+                    contract.token.internal_register_account(&contract_id);
+                }
+                contract
+            }
+            _ => env::state_read().expect("Contract is not initialized"),
+        };
+
+        Self::write_state_version(STATE_VERSION);
+        events::emit::config_change("state_version", &STATE_VERSION);
+        contract
+    }
+
+    #[private]
+    pub fn migrate_total_cash_callback(&mut self) {
+        let balance_of: Balance = match env::promise_result(0) {
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice::<U128>(&result)
+                .unwrap()
+                .into(),
+            _ => env::panic_str("Failed to get underlying balance of the market"),
+        };
+
+        self.total_cash = balance_of;
+        self.is_cash_synced = true;
+        events::emit::config_change("total_cash", &U128(balance_of));
+    }
+
+    /// Requests the underlying balance again in case it couldn't be read right after migration
+    pub fn sync_total_cash(&mut self) -> Promise {
+        self.assert_owner();
+        assert!(!self.is_cash_synced, "Cash is already synced with the underlying balance");
+        Self::request_total_cash_sync(self.underlying_token.clone())
+    }
+}
+
+/// Deploys the code passed as raw input and migrates the state, allowed to be called by admin only.
+/// Exported without near_bindgen, so the code isn't deserialized from JSON.
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+pub extern "C" fn upgrade() {
+    env::setup_panic_hook();
+
+    let contract: Contract = env::state_read().expect("Contract is not initialized");
+    contract.assert_owner();
+
+    let code = env::input().expect("Contract code is required");
+    let required_gas = env::used_gas() + GAS_FOR_UPGRADE;
+    let migrate_gas = env::prepaid_gas().0.checked_sub(required_gas.0).unwrap_or_else(|| {
+        env::panic_str(&format!("Not enough gas attached to upgrade, more than {} is required", required_gas.0))
+    });
+    Promise::new(env::current_account_id())
+        .deploy_contract(code)
+        .function_call(
+            "migrate".to_string(),
+            Vec::new(),
+            NO_DEPOSIT,
+            Gas(migrate_gas),
+        );
+}
@@ -1,6 +1,7 @@
 use crate::*;
 
 impl Contract {
+    /// Restores supplies on controller when underlying tokens weren't transferred, dtokens aren't burned yet
     pub fn withdraw_ft_transfer_fallback(
         &mut self,
         user_account: AccountId,
@@ -13,7 +14,14 @@ impl Contract {
             Balance::from(token_amount),
             Balance::from(dtoken_amount)
         );
-        //TODO: implement fallback method
+        controller::increase_supplies(
+            user_account,
+            self.get_contract_address(),
+            token_amount,
+            self.get_controller_address(),
+            NO_DEPOSIT,
+            self.terra_gas(10),
+        );
     }
 }
 
@@ -21,6 +29,10 @@ impl Contract {
 impl Contract {
 
     pub fn withdraw(&mut self, dtoken_amount: WBalance) -> Promise {
+        self.assert_no_flash_loan();
+        self.assert_cash_synced();
+        self.accrue_interest();
+        self.record_action(&env::signer_account_id(), true);
         return underlying_token::ft_balance_of(
             self.get_contract_address(),
             self.get_underlying_contract_address(),
@@ -35,6 +47,7 @@ impl Contract {
         ));
     }
 
+    #[private]
     pub fn withdraw_balance_of_callback(&mut self, dtoken_amount: Balance) -> Promise {
         let promise_success: bool = is_promise_success();
         assert_eq!(
@@ -52,8 +65,7 @@ impl Contract {
                 .into(),
         };
 
-        let exchange_rate: Balance = self.get_exchange_rate(WBalance::from(balance_of));
-        let token_amount: Balance = Balance::from(dtoken_amount) / exchange_rate;
+        let token_amount: Balance = self.get_underlying_amount(dtoken_amount, balance_of);
 
         return controller::withdraw_supplies(
             env::signer_account_id(),
@@ -69,10 +81,11 @@ impl Contract {
             dtoken_amount.into(),
             env::current_account_id().clone(),
             NO_DEPOSIT,
-            self.terra_gas(70),
+            self.terra_gas(90),
         ));
     }
 
+    #[private]
     pub fn withdraw_supplies_callback(
         &mut self,
         user_account: AccountId,
@@ -84,23 +97,22 @@ impl Contract {
         assert_eq!(promise_success, true, "Withdraw supplies has been failed");
 
         // Cross-contract call to market token
-        underlying_token::ft_transfer(
+        self.transfer_underlying(
             user_account,
             token_amount,
-            Some(format!("Withdraw with token_amount {}", Balance::from(token_amount))),
-            self.get_underlying_contract_address(),
-            ONE_YOCTO,
+            format!("Withdraw with token_amount {}", Balance::from(token_amount)),
             self.terra_gas(40),
         )
         .then(ext_self::withdraw_ft_transfer_call_callback(
-            dtoken_amount.into(),
             token_amount.into(),
+            dtoken_amount.into(),
             env::current_account_id().clone(),
             NO_DEPOSIT,
-            self.terra_gas(10),
+            self.terra_gas(30),
         ))
     }
 
+    #[private]
     pub fn withdraw_ft_transfer_call_callback(
         &mut self,
         token_amount: WBalance,
@@ -110,6 +122,8 @@ impl Contract {
 
         if promise_success {
             self.burn(&env::signer_account_id(), dtoken_amount);
+            self.total_cash = self.total_cash.saturating_sub(Balance::from(token_amount));
+            events::emit::withdraw(&env::signer_account_id(), token_amount.into(), dtoken_amount.into());
         } else {
             self.withdraw_ft_transfer_fallback(
                 env::signer_account_id(),
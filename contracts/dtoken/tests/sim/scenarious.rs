@@ -1,11 +1,17 @@
-use near_sdk::AccountId;
+use near_sdk::{AccountId, Balance};
 use near_sdk::json_types::U128;
-use near_sdk_sim::{call, ContractAccount, ExecutionResult, init_simulator, UserAccount, view};
-use controller::{Config as cConfig};
+use near_sdk::serde_json::json;
+use near_contract_standards::storage_management::StorageBalance;
+use near_sdk_sim::{call, ContractAccount, DEFAULT_GAS, deploy, ExecutionResult, init_simulator, to_yocto, UserAccount, view};
+use controller::{AccountPosition, Config as cConfig, GovernanceAction, PriceInfo};
+use general::{Price, PriceJsonList};
 use controller::ActionType;
 use controller::ActionType::{Supply, Borrow};
 use dtoken::Config as dConfig;
-use crate::utils::{init_controller, init_dtoken, init_utoken};
+use dtoken::{AccountInfo, MarketInfo};
+use test_utoken::FailureConfig;
+use test_oracle::PricePath;
+use crate::utils::{controller_baseline_wasm_bytes, controller_wasm_bytes, dtoken_baseline_wasm_bytes, dtoken_wasm_bytes, init_controller, init_dtoken, init_utoken, oracle_wasm_bytes, utoken_wasm_bytes};
 
 
 fn assert_failure(outcome: ExecutionResult, error_message: &str) {
@@ -21,6 +27,66 @@ fn view_balance(contract: &ContractAccount<controller::ContractContract>, action
     ).unwrap_json()
 }
 
+fn register_account(dtoken: &ContractAccount<dtoken::ContractContract>, controller: &ContractAccount<controller::ContractContract>, user: &UserAccount) {
+    call!(
+        user,
+        dtoken.storage_deposit(None, None),
+        deposit = to_yocto("0.1")
+    ).assert_success();
+
+    call!(
+        user,
+        controller.storage_deposit(None, None),
+        deposit = to_yocto("0.1")
+    ).assert_success();
+}
+
+/// Queues and executes the action right away, controllers of the scenarios are initialized without governance delay
+fn execute_governance_action(owner: &UserAccount, controller: &ContractAccount<controller::ContractContract>, action: GovernanceAction) {
+    let proposal_id: u64 = call!(
+        owner,
+        controller.queue_action(action),
+        deposit = 0
+    ).unwrap_json();
+
+    call!(
+        owner,
+        controller.execute_action(proposal_id),
+        deposit = 0
+    ).assert_success();
+}
+
+fn list_market(owner: &UserAccount, controller: &ContractAccount<controller::ContractContract>, market: AccountId) {
+    execute_governance_action(owner, controller, GovernanceAction::AddMarket { market });
+}
+
+/// Pushes prices of the markets from the oracle account of the controller config, each token is worth 1
+fn push_prices(root: &UserAccount, controller: &ContractAccount<controller::ContractContract>, markets: Vec<AccountId>) {
+    let oracle = root.create_user("oracle".parse().unwrap(), to_yocto("100"));
+    call!(
+        oracle,
+        controller.oracle_on_data(PriceJsonList {
+            block_height: 1,
+            price_list: markets.into_iter().map(|asset_id| Price { asset_id, value: 1, volatility: 100 }).collect()
+        }),
+        deposit = 0
+    ).assert_success();
+}
+
+/// Lets the user borrow up to the amount of market tokens, the user supplies the same amount to a collateral market
+fn provide_collateral(croot: &UserAccount, controller: &ContractAccount<controller::ContractContract>, market: AccountId, user: &UserAccount, amount: u128) {
+    let (collateral_utoken, collateral_dtoken) = deploy_market(croot, controller, "collateral");
+    push_prices(croot, controller, vec![market, collateral_dtoken.account_id()]);
+
+    call!(user, collateral_dtoken.storage_deposit(None, None), deposit = to_yocto("0.1")).assert_success();
+    call!(croot, collateral_utoken.mint(user.account_id(), U128(amount)), deposit = 0).assert_success();
+    call!(
+        user,
+        collateral_utoken.ft_transfer_call(collateral_dtoken.account_id(), U128(amount), None, json!({"action": "SUPPLY"}).to_string()),
+        deposit = 1
+    ).assert_success();
+}
+
 fn initialize_utoken(root: &UserAccount) -> (UserAccount, ContractAccount<test_utoken::ContractContract>, UserAccount) {
     let uroot = root.create_user("utoken".parse().unwrap(), 1200000000000000000000000000000);
     let (uroot, utoken, u_user) = init_utoken(
@@ -44,11 +110,8 @@ fn initialize_controller(root: &UserAccount) -> (UserAccount, ContractAccount<co
     );
     call!(
         croot,
-        controller.new(
-            cConfig{
-                owner_id: croot.account_id().clone(), 
-                oracle_account_id: "oracle".parse().unwrap()
-            }),
+        controller.new_with_governance_delay(
+            cConfig::new(croot.account_id().clone(), "oracle".parse().unwrap()), 0),
         deposit = 0
     )
         .assert_success();
@@ -82,8 +145,11 @@ fn base_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccount
      //  Initialize
  
      let (uroot, utoken, _u_user) = initialize_utoken(&root);
-     let (_croot, controller, _c_user) = initialize_controller(&root);
+     let (croot, controller, _c_user) = initialize_controller(&root);
      let (_droot, dtoken, d_user) = initialize_dtoken(&root, utoken.account_id(), controller.account_id());
+     register_account(&dtoken, &controller, &d_user);
+
+     list_market(&croot, &controller, dtoken.account_id());
  
      // Supply preparation 
      call!(
@@ -104,31 +170,7 @@ fn base_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccount
 }
 
 fn withdraw_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccount<controller::ContractContract>, ContractAccount<test_utoken::ContractContract>, UserAccount){
-    let (dtoken, controller, utoken, user) = base_fixture();
-
-    call!(
-        user,
-        dtoken.mint(&user.account_id(), U128(20)),
-        0,
-        100000000000000
-    ).assert_success();
-
-    call!(
-        user,
-        controller.increase_supplies(user.account_id(), dtoken.account_id(), U128(20)),
-        0,
-        100000000000000
-    ).assert_success();
-
-    call!(
-        user,
-        utoken.ft_transfer(
-            dtoken.account_id(), 
-            U128(20), 
-            Some(format!("Supply with token_amount 20"))),
-        1,
-        100000000000000
-    );
+    let (dtoken, controller, utoken, user) = supply_fixture();
 
     let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
     assert_eq!(user_balance, 20, "Balance should be 20");
@@ -137,41 +179,56 @@ fn withdraw_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAcc
 }
 
 fn repay_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccount<controller::ContractContract>, ContractAccount<test_utoken::ContractContract>, UserAccount) {
-    let (dtoken, controller, utoken, user) = base_fixture();
+    let root = init_simulator(None);
+
+    let (uroot, utoken, _u_user) = initialize_utoken(&root);
+    let (croot, controller, _c_user) = initialize_controller(&root);
+    let (_droot, dtoken, d_user) = initialize_dtoken(&root, utoken.account_id(), controller.account_id());
+    register_account(&dtoken, &controller, &d_user);
+    list_market(&croot, &controller, dtoken.account_id());
 
     call!(
-        user,
-        dtoken.increase_borrows(user.account_id(),U128(10)),
+        uroot,
+        utoken.mint(dtoken.account_id(), U128(10)),
+        0,
+        100000000000000
+    );
+
+    call!(
+        uroot,
+        utoken.mint(d_user.account_id(), U128(10)),
         0,
         100000000000000
+    );
+    provide_collateral(&croot, &controller, dtoken.account_id(), &d_user, 10);
+
+    call!(
+        d_user,
+        dtoken.borrow(U128(10)),
+        deposit = 0
     ).assert_success();
 
     let user_balance: u128 = view!(
         dtoken.get_borrows_by_account(
-            user.account_id()
+            d_user.account_id()
         )
     ).unwrap_json();
     assert_eq!(user_balance, 10, "Borrow balance on dtoken should be 10");
 
-    call!(
-        user,
-        controller.increase_borrows(user.account_id(), dtoken.account_id() ,U128(10)),
-        0,
-        100000000000000
-    ).assert_success();
-
-    let user_balance: u128 = view_balance(&controller, Borrow, user.account_id(), dtoken.account_id());
+    let user_balance: u128 = view_balance(&controller, Borrow, d_user.account_id(), dtoken.account_id());
     assert_eq!(user_balance, 10, "Borrow balance on controller should be 10");
 
-    (dtoken, controller, utoken, user)
+    (dtoken, controller, utoken, d_user)
 }
 
 fn borrow_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccount<controller::ContractContract>, ContractAccount<test_utoken::ContractContract>, UserAccount) {
     let root = init_simulator(None);
               
     let (uroot, utoken, _u_user) = initialize_utoken(&root);
-    let (_croot, controller, _c_user) = initialize_controller(&root);
+    let (croot, controller, _c_user) = initialize_controller(&root);
     let (_droot, dtoken, d_user) = initialize_dtoken(&root, utoken.account_id(), controller.account_id());
+    register_account(&dtoken, &controller, &d_user);
+    list_market(&croot, &controller, dtoken.account_id());
 
     call!(
         uroot,
@@ -186,6 +243,7 @@ fn borrow_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccou
         0,
         100000000000000
     );
+    provide_collateral(&croot, &controller, dtoken.account_id(), &d_user, 40);
     (dtoken, controller, utoken, d_user)
 
 }
@@ -314,6 +372,34 @@ fn scenario_supply() {
     
 }
 
+#[test]
+fn scenario_market_info() {
+    let (dtoken, _controller, utoken, user) = base_fixture();
+
+    call!(
+        user,
+        utoken.ft_transfer_call(
+            dtoken.account_id(),
+            U128(20),
+            Some("SUPPLY".to_string()),
+            r#"{"action":"SUPPLY"}"#.to_string()
+        ),
+        deposit = 1
+    ).assert_success();
+
+    let market_info: MarketInfo = view!(
+        dtoken.get_market_info()
+    ).unwrap_json();
+
+    assert_eq!(market_info.underlying_token_id, utoken.account_id(), "Underlying token should be utoken");
+    assert_eq!(market_info.tracked_cash, U128(20), "Cash should be 20");
+    assert_eq!(market_info.total_borrows, U128(0), "Total borrows should be 0");
+    assert_eq!(market_info.total_supply, U128(20), "Dtoken total supply should be 20");
+    assert_eq!(market_info.exchange_rate, U128(1), "Exchange rate should be 1");
+    assert_eq!(market_info.utilization, U128(0), "Utilization should be 0");
+    assert_eq!(market_info.supply_apy, U128(0), "Supply rate should be 0 w/o borrows");
+}
+
 #[test]
 fn scenario_withdraw_with_no_supply(){
     let (dtoken, _controller, _utoken, user) = base_fixture();
@@ -369,6 +455,42 @@ fn scenario_withdraw_less_same(){
 
 }
 
+#[test]
+fn scenario_withdraw_with_exchange_rate(){
+    let (dtoken, controller, utoken, user) = base_fixture();
+
+    call!(
+        user,
+        utoken.ft_transfer_call(dtoken.account_id(), U128(10), None, json!({"action": "SUPPLY"}).to_string()),
+        deposit = 1
+    ).assert_success();
+
+    // Tokens transferred to the market without supply make a dtoken worth 2 tokens
+    call!(
+        user,
+        utoken.ft_transfer(dtoken.account_id(), U128(10), None),
+        deposit = 1
+    ).assert_success();
+
+    let market_info: MarketInfo = view!(dtoken.get_market_info()).unwrap_json();
+    assert_eq!(market_info.tracked_cash, U128(10), "Directly transferred tokens shouldn't be tracked as cash");
+
+    call!(
+        user,
+        dtoken.withdraw(U128(10)),
+        deposit = 0
+    ).assert_success();
+
+    let dtoken_balance: U128 = view!(dtoken.ft_balance_of(user.account_id())).unwrap_json();
+    assert_eq!(dtoken_balance, U128(0), "All withdrawn dtokens should be burned");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 5, "Supplies should be decreased by withdrawn tokens");
+
+    let user_balance: String = view!(utoken.ft_balance_of(user.account_id())).unwrap_json();
+    assert_eq!(user_balance, 5.to_string(), "User should receive 5 utokens");
+}
+
 #[test]
 fn scenario_repay_no_borrow(){
     let (dtoken, _controller, utoken, user) = base_fixture();
@@ -507,6 +629,66 @@ fn scenario_borrow(){
     assert_eq!(dtoken_balance, 0.to_string(), "Dtoken balance on utoken should be 0");
 }
 
+#[test]
+fn scenario_borrow_more_than_collateral(){
+    let (dtoken, controller, utoken, user) = borrow_fixture();
+
+    let result = call!(
+        user,
+        dtoken.borrow(U128(41)),
+        deposit = 0
+    );
+    assert_failure(result, "Borrow operation is not allowed");
+
+    let user_balance: u128 = view_balance(&controller, Borrow, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 0, "Borrow balance on controller should be 0");
+
+    let user_balance: String = view!(
+        utoken.ft_balance_of(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, 0.to_string(), "User shouldn't receive utokens");
+}
+
+#[test]
+fn scenario_account_info(){
+    let (dtoken, _controller, _utoken, user) = borrow_fixture();
+
+    call!(
+        user,
+        dtoken.borrow(
+            U128(20)
+        ),
+        deposit = 0
+    ).assert_success();
+
+    let account_info: AccountInfo = view!(
+        dtoken.get_account_info(user.account_id())
+    ).unwrap_json();
+
+    assert_eq!(account_info.dtoken_balance, U128(0), "User shouldn't have dtokens w/o supply");
+    assert_eq!(account_info.underlying_balance, U128(0), "User shouldn't have underlying value w/o supply");
+    assert_eq!(account_info.borrow_balance, U128(20), "User borrow balance should be 20");
+    assert!(account_info.borrow_index.0 > 0, "Borrow index snapshot should be taken on borrow");
+    assert!(account_info.last_action_block.is_some(), "Borrow block height should be recorded");
+}
+
+#[test]
+fn scenario_same_block_guard_controller_only(){
+    let (dtoken, _controller, _utoken, user) = base_fixture();
+
+    let result = call!(
+        user,
+        dtoken.set_same_block_guard(true),
+        deposit = 0
+    );
+    assert_failure(result, "This functionality is allowed to be called by controller only");
+
+    let guard_enabled: bool = view!(
+        dtoken.get_same_block_guard()
+    ).unwrap_json();
+    assert_eq!(guard_enabled, false, "Same block guard should stay disabled");
+}
+
 #[test]
 fn scenatio_borrow_more_than_on_dtoken(){
     let (dtoken, controller, utoken, user) = borrow_fixture();
@@ -538,3 +720,1138 @@ fn scenatio_borrow_more_than_on_dtoken(){
     assert_eq!(dtoken_balance, 20.to_string(), "Dtoken balance on utoken should be 20");
 }
 
+
+fn supply_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccount<controller::ContractContract>, ContractAccount<test_utoken::ContractContract>, UserAccount) {
+    let (dtoken, controller, utoken, user) = base_fixture();
+
+    call!(
+        user,
+        utoken.ft_transfer_call(
+            dtoken.account_id(),
+            U128(20),
+            Some("SUPPLY".to_string()),
+            r#"{"action":"SUPPLY"}"#.to_string()
+        ),
+        deposit = 1
+    ).assert_success();
+
+    (dtoken, controller, utoken, user)
+}
+
+#[test]
+fn scenario_dtoken_transfer(){
+    let (dtoken, controller, _utoken, user) = supply_fixture();
+    let receiver = user.create_user("receiver".parse().unwrap(), to_yocto("100"));
+    register_account(&dtoken, &controller, &receiver);
+
+    call!(
+        user,
+        dtoken.ft_transfer(receiver.account_id(), U128(5), None),
+        deposit = 1
+    ).assert_success();
+
+    let user_balance: U128 = view!(
+        dtoken.ft_balance_of(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, U128(15), "User dtoken balance should be 15");
+
+    let receiver_balance: U128 = view!(
+        dtoken.ft_balance_of(receiver.account_id())
+    ).unwrap_json();
+    assert_eq!(receiver_balance, U128(5), "Receiver dtoken balance should be 5");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 15, "User supplies on controller should be 15");
+
+    let receiver_balance: u128 = view_balance(&controller, Supply, receiver.account_id(), dtoken.account_id());
+    assert_eq!(receiver_balance, 5, "Receiver supplies on controller should be 5");
+}
+
+#[test]
+fn scenario_dtoken_transfer_worth_no_tokens(){
+    let (dtoken, controller, utoken, user) = base_fixture();
+    let receiver = user.create_user("receiver".parse().unwrap(), to_yocto("100"));
+    register_account(&dtoken, &controller, &receiver);
+
+    call!(
+        user,
+        utoken.ft_transfer_call(dtoken.account_id(), U128(10), None, json!({"action": "SUPPLY"}).to_string()),
+        deposit = 1
+    ).assert_success();
+
+    // Tokens transferred to the market without supply make a single dtoken convert to no tokens the way withdraw does
+    call!(
+        user,
+        utoken.ft_transfer(dtoken.account_id(), U128(10), None),
+        deposit = 1
+    ).assert_success();
+
+    let result = call!(
+        user,
+        dtoken.ft_transfer(receiver.account_id(), U128(1), None),
+        deposit = 1
+    );
+    assert_failure(result, "Transfer of 1 dtokens is refused, they are worth no underlying tokens");
+
+    let user_balance: U128 = view!(
+        dtoken.ft_balance_of(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, U128(10), "Refused dtokens should come back to the sender");
+
+    let receiver_balance: U128 = view!(
+        dtoken.ft_balance_of(receiver.account_id())
+    ).unwrap_json();
+    assert_eq!(receiver_balance, U128(0), "Receiver shouldn't get dtokens without supplies");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 10, "User supplies on controller should stay 10");
+
+    // Two dtokens are converted to a token, so supplies move along with them
+    call!(
+        user,
+        dtoken.ft_transfer(receiver.account_id(), U128(2), None),
+        deposit = 1
+    ).assert_success();
+
+    let receiver_balance: u128 = view_balance(&controller, Supply, receiver.account_id(), dtoken.account_id());
+    assert_eq!(receiver_balance, 1, "Receiver supplies on controller should be 1");
+}
+
+#[test]
+fn scenario_dtoken_transfer_to_unregistered(){
+    let (dtoken, _controller, _utoken, user) = supply_fixture();
+    let receiver = user.create_user("receiver".parse().unwrap(), to_yocto("100"));
+
+    let result = call!(
+        user,
+        dtoken.ft_transfer(receiver.account_id(), U128(5), None),
+        deposit = 1
+    );
+    assert_failure(result, "isn't registered, storage_deposit is required");
+}
+
+#[test]
+fn scenario_dtoken_transfer_unhealthy(){
+    let (dtoken, controller, _utoken, user) = supply_fixture();
+    let receiver = user.create_user("receiver".parse().unwrap(), to_yocto("100"));
+    register_account(&dtoken, &controller, &receiver);
+    let oracle = user.create_user("oracle".parse().unwrap(), to_yocto("100"));
+
+    call!(
+        oracle,
+        controller.oracle_on_data(PriceJsonList {
+            block_height: 1,
+            price_list: vec![Price { asset_id: dtoken.account_id(), value: 1, volatility: 100 }]
+        }),
+        deposit = 0
+    ).assert_success();
+
+    let result = call!(
+        user,
+        controller.increase_borrows(user.account_id(), dtoken.account_id(), U128(15)),
+        deposit = 0
+    );
+    assert_failure(result, "This functionality is allowed to be called by listed market");
+
+    call!(
+        user,
+        dtoken.borrow(U128(15)),
+        deposit = 0
+    ).assert_success();
+
+    call!(
+        user,
+        dtoken.ft_transfer(receiver.account_id(), U128(10), None),
+        deposit = 1
+    );
+
+    let user_balance: U128 = view!(
+        dtoken.ft_balance_of(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, U128(20), "Transfer making user liquidatable should be refunded");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 20, "User supplies on controller should stay 20");
+
+    let receiver_balance: u128 = view_balance(&controller, Supply, receiver.account_id(), dtoken.account_id());
+    assert_eq!(receiver_balance, 0, "Receiver supplies on controller should be 0");
+}
+
+#[test]
+fn scenario_dtoken_transfer_call_refund(){
+    let (dtoken, controller, _utoken, user) = supply_fixture();
+
+    // Controller has no ft_on_transfer, so the whole amount is refunded
+    call!(
+        user,
+        dtoken.storage_deposit(Some(controller.account_id()), None),
+        deposit = to_yocto("0.1")
+    ).assert_success();
+    call!(
+        user,
+        controller.storage_deposit(Some(controller.account_id()), None),
+        deposit = to_yocto("0.1")
+    ).assert_success();
+
+    call!(
+        user,
+        dtoken.ft_transfer_call(controller.account_id(), U128(10), None, "".to_string()),
+        deposit = 1
+    );
+
+    let user_balance: U128 = view!(
+        dtoken.ft_balance_of(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, U128(20), "Refunded dtokens should come back to the sender");
+
+    let receiver_balance: U128 = view!(
+        dtoken.ft_balance_of(controller.account_id())
+    ).unwrap_json();
+    assert_eq!(receiver_balance, U128(0), "Receiver shouldn't keep refunded dtokens");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 20, "Refunded supplies should come back to the sender");
+
+    let receiver_balance: u128 = view_balance(&controller, Supply, controller.account_id(), dtoken.account_id());
+    assert_eq!(receiver_balance, 0, "Receiver supplies on controller should be 0");
+}
+
+#[test]
+fn scenario_storage_unregister(){
+    let (dtoken, controller, _utoken, user) = base_fixture();
+    let account = user.create_user("unregistered".parse().unwrap(), to_yocto("100"));
+    register_account(&dtoken, &controller, &account);
+
+    let result = call!(
+        account,
+        dtoken.storage_unregister(Some(true)),
+        deposit = 1
+    );
+    assert_failure(result, "Force unregister isn't supported, withdraw and repay first");
+
+    let near_balance = account.account().unwrap().amount;
+    call!(
+        account,
+        dtoken.storage_unregister(None),
+        deposit = 1
+    ).assert_success();
+
+    let storage: Option<StorageBalance> = view!(
+        dtoken.storage_balance_of(account.account_id())
+    ).unwrap_json();
+    assert!(storage.is_none(), "Account should be unregistered");
+    assert!(account.account().unwrap().amount > near_balance, "Paid storage deposit should be refunded");
+}
+
+#[test]
+fn scenario_internal_methods_are_not_exposed(){
+    let (dtoken, _controller, _utoken, user) = base_fixture();
+    let account_args = json!({"account_id": user.account_id(), "amount": "20"});
+    let borrow_args = json!({"account": user.account_id(), "token_amount": "20"});
+
+    let methods = [
+        ("mint", account_args.clone()),
+        ("burn", account_args),
+        ("increase_borrows", borrow_args.clone()),
+        ("decrease_borrows", borrow_args.clone()),
+        ("set_borrows", borrow_args),
+        ("set_total_reserves", json!({"amount": "20"})),
+        ("set_total_borrows", json!({"amount": "20"})),
+        ("supply", json!({"token_amount": "20"})),
+        ("repay", json!({"token_amount": "20"})),
+    ];
+
+    for (method, args) in methods {
+        let result = user.call(dtoken.account_id(), method, args.to_string().as_bytes(), DEFAULT_GAS, 0);
+        assert_failure(result, "MethodNotFound");
+    }
+
+    let dtoken_balance: U128 = view!(
+        dtoken.ft_balance_of(user.account_id())
+    ).unwrap_json();
+    assert_eq!(dtoken_balance, U128(0), "User shouldn't be able to mint dtokens");
+}
+
+#[test]
+fn scenario_callbacks_are_private(){
+    let (dtoken, _controller, _utoken, user) = base_fixture();
+    let transfer_args = json!({"sender_id": user.account_id(), "receiver_id": user.account_id(), "amount": "20", "memo": null});
+
+    let callbacks = [
+        ("supply_balance_of_callback", json!({"token_amount": "20"})),
+        ("controller_increase_supplies_callback", json!({"amount": "20", "dtoken_amount": "20"})),
+        ("make_borrow_callback", json!({"token_amount": "20"})),
+        ("borrow_ft_transfer_callback", json!({"token_amount": "20"})),
+        ("controller_decrease_borrows_callback", json!({})),
+        ("controller_repay_borrows_callback", json!({"amount": "20"})),
+        ("withdraw_balance_of_callback", json!({"dtoken_amount": 20})),
+        ("withdraw_supplies_callback", json!({"user_account": user.account_id(), "token_amount": "20", "dtoken_amount": "20"})),
+        ("withdraw_ft_transfer_call_callback", json!({"token_amount": "20", "dtoken_amount": "20"})),
+        ("ft_transfer_controller_callback", transfer_args),
+        ("ft_transfer_call_controller_callback", json!({"sender_id": user.account_id(), "receiver_id": user.account_id(), "amount": "20", "memo": null, "msg": ""})),
+        ("ft_resolve_transfer", json!({"sender_id": user.account_id(), "receiver_id": user.account_id(), "amount": "20"})),
+        ("ft_transfer_balance_of_callback", json!({"sender_id": user.account_id(), "receiver_id": user.account_id(), "amount": "20", "memo": null, "msg": null})),
+        ("ft_transfer_refused", json!({"amount": "20"})),
+        ("ft_refund_balance_of_callback", json!({"sender_id": user.account_id(), "receiver_id": user.account_id(), "amount": "20"})),
+        ("ft_refund_controller_callback", json!({"sender_id": user.account_id(), "receiver_id": user.account_id(), "amount": "20"})),
+        ("flash_loan_transfer_callback", json!({"amount": "20", "receiver": user.account_id(), "msg": "", "lock_block": 0})),
+        ("flash_loan_resolve_callback", json!({"receiver": user.account_id(), "amount": "20", "balance_before": "0", "lock_block": 0})),
+        ("flash_loan_not_repaid", json!({"receiver": user.account_id(), "shortfall": "20"})),
+        ("supply_native_callback", json!({"amount": "20"})),
+        ("supply_native_resolve_callback", json!({"amount": "20"})),
+        ("unwrap_near_callback", json!({"receiver": user.account_id(), "amount": "20"})),
+    ];
+
+    for (method, args) in callbacks {
+        let result = user.call(dtoken.account_id(), method, args.to_string().as_bytes(), DEFAULT_GAS, 0);
+        assert_failure(result, format!("Method {} is private", method).as_str());
+    }
+
+    let user_balance: u128 = view!(
+        dtoken.get_borrows_by_account(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, 0, "User shouldn't be able to change borrows through callbacks");
+}
+
+/// Deploys the current code over the released one the way the account owner does and migrates the state
+fn upgrade_from_baseline(contract: &UserAccount, code: &[u8]) {
+    let result = contract
+        .create_transaction(contract.account_id())
+        .deploy_contract(code.to_vec())
+        .function_call("migrate".to_string(), Vec::new(), DEFAULT_GAS, 0)
+        .submit();
+    assert!(result.promise_errors().is_empty(), "Migration from the released state should succeed");
+}
+
+/// Creates positions with the released contracts and upgrades both of them to the current code
+fn baseline_fixture(supplied: Balance, borrowed: Balance) -> (UserAccount, UserAccount, UserAccount, ContractAccount<dtoken::ContractContract>, ContractAccount<controller::ContractContract>, UserAccount) {
+    let root = init_simulator(None);
+
+    let (uroot, utoken, _u_user) = initialize_utoken(&root);
+    let croot = root.create_user("controller".parse().unwrap(), 1200000000000000000000000000000);
+    let controller = deploy!(
+        contract: controller::ContractContract,
+        contract_id: AccountId::new_unchecked("controller_contract".to_string()),
+        bytes: controller_baseline_wasm_bytes(),
+        signer_account: croot
+    );
+    let droot = root.create_user("dtoken".parse().unwrap(), 1200000000000000000000000000000);
+    let dtoken = deploy!(
+        contract: dtoken::ContractContract,
+        contract_id: AccountId::new_unchecked("dtoken_contract".to_string()),
+        bytes: dtoken_baseline_wasm_bytes(),
+        signer_account: droot
+    );
+    let user = root.create_user("user_account".parse().unwrap(), to_yocto("1000000"));
+
+    // Released contracts are driven by raw calls, their interface differs from the current one
+    croot.call(
+        controller.account_id(),
+        "new",
+        json!({"config": {"owner_id": croot.account_id(), "oracle_account_id": "oracle"}}).to_string().as_bytes(),
+        DEFAULT_GAS,
+        0,
+    ).assert_success();
+    droot.call(
+        dtoken.account_id(),
+        "new",
+        json!({"config": {
+            "initial_exchange_rate": "1",
+            "underlying_token_id": utoken.account_id(),
+            "owner_id": droot.account_id(),
+            "controller_account_id": controller.account_id(),
+        }}).to_string().as_bytes(),
+        DEFAULT_GAS,
+        0,
+    ).assert_success();
+
+    call!(uroot, utoken.mint(dtoken.account_id(), U128(0)), 0, 100000000000000).assert_success();
+    call!(uroot, utoken.mint(user.account_id(), U128(supplied)), 0, 100000000000000).assert_success();
+
+    user.call(
+        dtoken.account_id(),
+        "mint",
+        json!({"account_id": user.account_id(), "amount": U128(supplied)}).to_string().as_bytes(),
+        DEFAULT_GAS,
+        0,
+    ).assert_success();
+    user.call(
+        controller.account_id(),
+        "increase_supplies",
+        json!({"account": user.account_id(), "token_address": dtoken.account_id(), "token_amount": U128(supplied)}).to_string().as_bytes(),
+        DEFAULT_GAS,
+        0,
+    ).assert_success();
+    call!(
+        user,
+        utoken.ft_transfer(dtoken.account_id(), U128(supplied), None),
+        deposit = 1
+    ).assert_success();
+    user.call(
+        dtoken.account_id(),
+        "borrow",
+        json!({"token_amount": U128(borrowed)}).to_string().as_bytes(),
+        DEFAULT_GAS,
+        0,
+    ).assert_success();
+
+    upgrade_from_baseline(&dtoken.user_account, dtoken_wasm_bytes());
+    upgrade_from_baseline(&controller.user_account, controller_wasm_bytes());
+
+    (root, croot, droot, dtoken, controller, user)
+}
+
+#[test]
+fn scenario_upgrade_keeps_positions(){
+    let (_root, croot, droot, dtoken, controller, user) = baseline_fixture(20, 5);
+
+    let state_version: u32 = view!(dtoken.get_state_version()).unwrap_json();
+    assert_eq!(state_version, dtoken::STATE_VERSION, "Dtoken state version should be current");
+
+    let state_version: u32 = view!(controller.get_state_version()).unwrap_json();
+    assert_eq!(state_version, controller::STATE_VERSION, "Controller state version should be current");
+
+    let user_balance: U128 = view!(
+        dtoken.ft_balance_of(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, U128(20), "User dtoken balance should survive upgrade");
+
+    let market_info: MarketInfo = view!(dtoken.get_market_info()).unwrap_json();
+    assert_eq!(market_info.tracked_cash, U128(15), "Cash should be synced with the underlying balance");
+
+    let result = call!(droot, dtoken.sync_total_cash(), deposit = 0);
+    assert_failure(result, "Cash is already synced with the underlying balance");
+
+    let contract_storage: Option<StorageBalance> = view!(
+        dtoken.storage_balance_of(dtoken.account_id())
+    ).unwrap_json();
+    assert!(contract_storage.is_some(), "Dtoken contract account should be registered to escrow transfers");
+
+    let user_balance: u128 = view!(
+        dtoken.get_borrows_by_account(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, 5, "User borrows on dtoken should survive upgrade");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 20, "User supplies on controller should survive upgrade");
+
+    let user_balance: u128 = view_balance(&controller, Borrow, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 5, "User borrows on controller should survive upgrade");
+
+    // Further upgrades go through the upgrade method of the migrated contracts
+    let result = user.call(dtoken.account_id(), "upgrade", dtoken_wasm_bytes(), DEFAULT_GAS, 0);
+    assert_failure(result, "This functionality is allowed to be called by admin only");
+
+    let result = user.call(controller.account_id(), "upgrade", controller_wasm_bytes(), DEFAULT_GAS, 0);
+    assert_failure(result, "This functionality is allowed to be called by admin only");
+
+    let result = droot.call(dtoken.account_id(), "upgrade", dtoken_wasm_bytes(), DEFAULT_GAS, 0);
+    assert!(result.promise_errors().is_empty(), "Dtoken upgrade should succeed");
+
+    let result = croot.call(controller.account_id(), "upgrade", controller_wasm_bytes(), DEFAULT_GAS, 0);
+    assert!(result.promise_errors().is_empty(), "Controller upgrade should succeed");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 20, "User supplies on controller should survive the second upgrade");
+}
+
+#[test]
+fn scenario_upgrade_accrues_interest_of_legacy_borrows(){
+    let amount = to_yocto("1");
+    let (root, _croot, _droot, dtoken, _controller, user) = baseline_fixture(20 * amount, 5 * amount);
+
+    // Borrows of the released contract have no index snapshot
+    let account_info: AccountInfo = view!(
+        dtoken.get_account_info(user.account_id())
+    ).unwrap_json();
+    assert_eq!(account_info.borrow_index, U128(dtoken::INITIAL_BORROW_INDEX), "Legacy borrow should accrue since migration");
+    assert_eq!(account_info.borrow_balance, U128(5 * amount), "Nothing should be accrued right after migration");
+
+    root.borrow_runtime_mut().produce_blocks(100).unwrap();
+
+    // Interest is accrued before the controller is asked, so the accrual is kept whatever it answers
+    user.call(
+        dtoken.account_id(),
+        "borrow",
+        json!({"token_amount": "1"}).to_string().as_bytes(),
+        DEFAULT_GAS,
+        0,
+    );
+
+    let market_info: MarketInfo = view!(dtoken.get_market_info()).unwrap_json();
+    let account_info: AccountInfo = view!(
+        dtoken.get_account_info(user.account_id())
+    ).unwrap_json();
+    assert!(market_info.total_borrows.0 > 5 * amount, "Interest should be accrued on total borrows");
+    assert!(account_info.borrow_balance.0 > 5 * amount, "Legacy borrower should owe the accrued interest");
+    assert_eq!(
+        market_info.total_borrows, account_info.borrow_balance,
+        "Interest owed by the only borrower should match the interest accrued by the market"
+    );
+}
+
+fn flash_loan_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccount<controller::ContractContract>, ContractAccount<test_utoken::ContractContract>, UserAccount, UserAccount) {
+    let root = init_simulator(None);
+
+    let (uroot, utoken, _u_user) = initialize_utoken(&root);
+    let (croot, controller, _c_user) = initialize_controller(&root);
+    let (droot, dtoken, user) = initialize_dtoken(&root, utoken.account_id(), controller.account_id());
+    register_account(&dtoken, &controller, &user);
+    list_market(&croot, &controller, dtoken.account_id());
+
+    call!(
+        uroot,
+        utoken.mint(user.account_id(), U128(20)),
+        0,
+        100000000000000
+    );
+
+    // Utoken contract acts as flash loan receiver and pays the fee from its own balance
+    call!(
+        uroot,
+        utoken.mint(utoken.account_id(), U128(10)),
+        0,
+        100000000000000
+    );
+
+    call!(
+        user,
+        utoken.ft_transfer_call(
+            dtoken.account_id(),
+            U128(20),
+            Some("SUPPLY".to_string()),
+            r#"{"action":"SUPPLY"}"#.to_string()
+        ),
+        deposit = 1
+    ).assert_success();
+
+    // 10% fee
+    execute_governance_action(
+        &croot,
+        &controller,
+        GovernanceAction::SetFlashLoanFee { market: dtoken.account_id(), fee: U128(1000) },
+    );
+
+    execute_governance_action(
+        &croot,
+        &controller,
+        GovernanceAction::AddFlashLoanReceiver { market: dtoken.account_id(), receiver: utoken.account_id() },
+    );
+
+    (dtoken, controller, utoken, user, droot)
+}
+
+#[test]
+fn scenario_flash_loan_repaid(){
+    let (dtoken, controller, utoken, user, _droot) = flash_loan_fixture();
+
+    call!(
+        user,
+        dtoken.flash_loan(U128(10), utoken.account_id(), "REPAY".to_string()),
+        0,
+        300000000000000
+    ).assert_success();
+
+    let market_info: MarketInfo = view!(dtoken.get_market_info()).unwrap_json();
+    assert_eq!(market_info.total_reserves, U128(1), "Flash loan fee should be credited to reserves");
+    assert_eq!(market_info.tracked_cash, U128(21), "Cash should include the fee");
+    assert_eq!(market_info.total_borrows, U128(0), "Flash loan shouldn't stay as a borrow");
+
+    let dtoken_balance: String = view!(
+        utoken.ft_balance_of(dtoken.account_id())
+    ).unwrap_json();
+    assert_eq!(dtoken_balance, 21.to_string(), "Dtoken balance on utoken should be 21");
+
+    let user_balance: u128 = view_balance(&controller, Borrow, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 0, "Borrow balance on controller should be 0");
+}
+
+#[test]
+fn scenario_flash_loan_not_repaid(){
+    let (dtoken, controller, utoken, user, _droot) = flash_loan_fixture();
+
+    let result = call!(
+        user,
+        dtoken.flash_loan(U128(10), utoken.account_id(), "KEEP".to_string()),
+        0,
+        300000000000000
+    );
+    assert_failure(result, "Flash loan wasn't repaid by");
+
+    let market_info: MarketInfo = view!(dtoken.get_market_info()).unwrap_json();
+    assert_eq!(market_info.total_reserves, U128(0), "Fee shouldn't be credited w/o repayment");
+    assert_eq!(market_info.tracked_cash, U128(10), "Cash should be 10");
+
+    let user_balance: u128 = view!(
+        dtoken.get_borrows_by_account(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, 0, "Unreturned flash loan shouldn't become a borrow on dtoken");
+
+    let user_balance: u128 = view_balance(&controller, Borrow, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 0, "Unreturned flash loan shouldn't become a borrow on controller");
+
+    // Lock is released by the failed transaction
+    call!(
+        user,
+        dtoken.flash_loan(U128(5), utoken.account_id(), "REPAY".to_string()),
+        0,
+        300000000000000
+    ).assert_success();
+}
+
+#[test]
+fn scenario_flash_loan_receiver_not_approved(){
+    let (dtoken, _controller, _utoken, user, droot) = flash_loan_fixture();
+
+    let result = call!(
+        user,
+        dtoken.flash_loan(U128(10), droot.account_id(), "REPAY".to_string()),
+        0,
+        300000000000000
+    );
+    assert_failure(result, "isn't approved by governance");
+}
+
+#[test]
+fn scenario_flash_loan_more_than_cash(){
+    let (dtoken, _controller, utoken, user, _droot) = flash_loan_fixture();
+
+    let result = call!(
+        user,
+        dtoken.flash_loan(U128(30), utoken.account_id(), "REPAY".to_string()),
+        0,
+        300000000000000
+    );
+    assert_failure(result, "Not enough liquidity for flash loan");
+}
+
+#[test]
+fn scenario_flash_loan_fee_controller_only(){
+    let (dtoken, _controller, _utoken, user, droot) = flash_loan_fixture();
+
+    let result = call!(
+        user,
+        dtoken.set_flash_loan_fee(U128(0)),
+        deposit = 0
+    );
+    assert_failure(result, "This functionality is allowed to be called by controller only");
+
+    let result = call!(
+        droot,
+        dtoken.set_flash_loan_fee(U128(0)),
+        deposit = 0
+    );
+    assert_failure(result, "This functionality is allowed to be called by controller only");
+
+    let fee: U128 = view!(dtoken.get_flash_loan_fee()).unwrap_json();
+    assert_eq!(fee, U128(1000), "Flash loan fee should stay 10%");
+}
+
+#[test]
+fn scenario_bad_debt_write_off_controller_only(){
+    let (dtoken, _controller, _utoken, user) = borrow_fixture();
+
+    let result = call!(
+        user,
+        dtoken.write_off_bad_debt(user.account_id()),
+        deposit = 0
+    );
+    assert_failure(result, "This functionality is allowed to be called by controller only");
+
+    let total_borrows: u128 = view!(dtoken.get_total_borrows()).unwrap_json();
+    assert_eq!(total_borrows, 0, "Total borrows shouldn't be changed");
+}
+
+#[test]
+fn scenario_seize_controller_only(){
+    let (dtoken, _controller, utoken, user) = base_fixture();
+
+    call!(
+        user,
+        utoken.ft_transfer_call(dtoken.account_id(), U128(20), None, json!({"action": "SUPPLY"}).to_string()),
+        deposit = 1
+    ).assert_success();
+
+    let result = call!(
+        user,
+        dtoken.seize(user.account_id(), dtoken.account_id(), U128(20), U128(0)),
+        deposit = 0
+    );
+    assert_failure(result, "This functionality is allowed to be called by controller only");
+
+    let dtoken_balance: U128 = view!(dtoken.ft_balance_of(user.account_id())).unwrap_json();
+    assert_ne!(dtoken_balance, U128(0), "Dtokens shouldn't be seized");
+}
+
+fn native_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccount<controller::ContractContract>, ContractAccount<test_utoken::ContractContract>, UserAccount) {
+    let root = init_simulator(None);
+
+    // Utoken acts as wNEAR contract
+    let (uroot, utoken, _u_user) = initialize_utoken(&root);
+    let (croot, controller, _c_user) = initialize_controller(&root);
+    let (_droot, dtoken, user) = initialize_dtoken(&root, utoken.account_id(), controller.account_id());
+    register_account(&dtoken, &controller, &user);
+    list_market(&croot, &controller, dtoken.account_id());
+
+    call!(
+        uroot,
+        utoken.mint(dtoken.account_id(), U128(0)),
+        0,
+        100000000000000
+    );
+
+    execute_governance_action(
+        &croot,
+        &controller,
+        GovernanceAction::SetNativeMarket { market: dtoken.account_id(), enabled: true },
+    );
+    push_prices(&croot, &controller, vec![dtoken.account_id()]);
+
+    call!(
+        user,
+        dtoken.supply_native(),
+        to_yocto("10"),
+        300000000000000
+    ).assert_success();
+
+    (dtoken, controller, utoken, user)
+}
+
+#[test]
+fn scenario_native_supply(){
+    let (dtoken, controller, utoken, user) = native_fixture();
+
+    let user_balance: U128 = view!(
+        dtoken.ft_balance_of(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, U128(to_yocto("10")), "User should receive dtokens for attached NEAR");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, to_yocto("10"), "User supplies on controller should be 10 NEAR");
+
+    let dtoken_balance: U128 = view!(
+        utoken.ft_balance_of(dtoken.account_id())
+    ).unwrap_json();
+    assert_eq!(dtoken_balance, U128(to_yocto("10")), "Attached NEAR should be wrapped");
+}
+
+#[test]
+fn scenario_native_withdraw(){
+    let (dtoken, controller, utoken, user) = native_fixture();
+    let near_balance = user.account().unwrap().amount;
+
+    call!(
+        user,
+        dtoken.withdraw(U128(to_yocto("4"))),
+        0,
+        300000000000000
+    ).assert_success();
+
+    let received = user.account().unwrap().amount - near_balance;
+    assert!(received > to_yocto("3.9"), "User should receive native NEAR on withdraw");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, to_yocto("6"), "User supplies on controller should be 6 NEAR");
+
+    let dtoken_balance: U128 = view!(
+        utoken.ft_balance_of(dtoken.account_id())
+    ).unwrap_json();
+    assert_eq!(dtoken_balance, U128(to_yocto("6")), "Withdrawn wNEAR should be unwrapped");
+}
+
+#[test]
+fn scenario_native_borrow(){
+    let (dtoken, _controller, _utoken, user) = native_fixture();
+    let near_balance = user.account().unwrap().amount;
+
+    call!(
+        user,
+        dtoken.borrow(U128(to_yocto("2"))),
+        0,
+        300000000000000
+    ).assert_success();
+
+    let received = user.account().unwrap().amount - near_balance;
+    assert!(received > to_yocto("1.9"), "User should receive native NEAR on borrow");
+
+    let user_balance: u128 = view!(
+        dtoken.get_borrows_by_account(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, to_yocto("2"), "User borrow balance on dtoken should be 2 NEAR");
+}
+
+#[test]
+fn scenario_supply_native_not_native_market(){
+    let (dtoken, _controller, _utoken, user) = base_fixture();
+
+    let result = call!(
+        user,
+        dtoken.supply_native(),
+        to_yocto("1"),
+        300000000000000
+    );
+    assert_failure(result, "doesn't support native NEAR");
+}
+
+#[test]
+fn scenario_native_market_controller_only(){
+    let (dtoken, _controller, _utoken, user) = base_fixture();
+
+    let result = call!(
+        user,
+        dtoken.set_native_market(true),
+        deposit = 0
+    );
+    assert_failure(result, "This functionality is allowed to be called by controller only");
+}
+
+fn failure_fixture() -> (ContractAccount<dtoken::ContractContract>, ContractAccount<controller::ContractContract>, ContractAccount<test_utoken::ContractContract>, UserAccount, UserAccount) {
+    let root = init_simulator(None);
+
+    let (uroot, utoken, _u_user) = initialize_utoken(&root);
+    let (croot, controller, _c_user) = initialize_controller(&root);
+    let (_droot, dtoken, user) = initialize_dtoken(&root, utoken.account_id(), controller.account_id());
+    register_account(&dtoken, &controller, &user);
+    list_market(&croot, &controller, dtoken.account_id());
+
+    call!(
+        uroot,
+        utoken.mint(dtoken.account_id(), U128(20)),
+        0,
+        100000000000000
+    );
+
+    call!(
+        uroot,
+        utoken.mint(user.account_id(), U128(20)),
+        0,
+        100000000000000
+    );
+    provide_collateral(&croot, &controller, dtoken.account_id(), &user, 20);
+
+    (dtoken, controller, utoken, uroot, user)
+}
+
+fn set_failure_config(utoken: &ContractAccount<test_utoken::ContractContract>, uroot: &UserAccount, config: FailureConfig) {
+    call!(
+        uroot,
+        utoken.set_failure_config(config),
+        deposit = 0
+    ).assert_success();
+}
+
+fn view_utoken_balance(utoken: &ContractAccount<test_utoken::ContractContract>, account: AccountId) -> String {
+    view!(
+        utoken.ft_balance_of(account)
+    ).unwrap_json()
+}
+
+#[test]
+fn scenario_failure_config_admin_only(){
+    let (_dtoken, _controller, utoken, _uroot, user) = failure_fixture();
+
+    let result = call!(
+        user,
+        utoken.set_failure_config(FailureConfig { fail_transfers: 1, ..Default::default() }),
+        deposit = 0
+    );
+    assert_failure(result, "This functionality is allowed to be called by admin only");
+}
+
+#[test]
+fn scenario_borrow_transfer_failure(){
+    let (dtoken, controller, utoken, uroot, user) = failure_fixture();
+    set_failure_config(&utoken, &uroot, FailureConfig { fail_transfers: 1, ..Default::default() });
+
+    call!(
+        user,
+        dtoken.borrow(U128(10)),
+        deposit = 0
+    );
+
+    let user_balance: u128 = view_balance(&controller, Borrow, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 0, "Borrow on controller should be reverted");
+
+    let user_balance: u128 = view!(
+        dtoken.get_borrows_by_account(user.account_id())
+    ).unwrap_json();
+    assert_eq!(user_balance, 0, "Borrow on dtoken shouldn't be recorded");
+    assert_eq!(view_utoken_balance(&utoken, user.account_id()), 20.to_string(), "User utoken balance should stay 20");
+
+    // Only the next transfer fails
+    call!(
+        user,
+        dtoken.borrow(U128(10)),
+        deposit = 0
+    ).assert_success();
+
+    let user_balance: u128 = view_balance(&controller, Borrow, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 10, "Borrow on controller should be 10");
+    assert_eq!(view_utoken_balance(&utoken, user.account_id()), 30.to_string(), "User utoken balance should be 30");
+}
+
+#[test]
+fn scenario_withdraw_transfer_failure(){
+    let (dtoken, controller, utoken, uroot, user) = failure_fixture();
+
+    call!(
+        user,
+        utoken.ft_transfer_call(dtoken.account_id(), U128(20), None, json!({"action": "SUPPLY"}).to_string()),
+        deposit = 1
+    ).assert_success();
+    set_failure_config(&utoken, &uroot, FailureConfig { fail_transfers: 1, ..Default::default() });
+
+    call!(
+        user,
+        dtoken.withdraw(U128(10)),
+        deposit = 0
+    );
+
+    let dtoken_balance: U128 = view!(dtoken.ft_balance_of(user.account_id())).unwrap_json();
+    assert_eq!(dtoken_balance, U128(20), "Dtokens shouldn't be burned");
+    assert_eq!(view_utoken_balance(&utoken, user.account_id()), 0.to_string(), "User shouldn't receive utokens");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 20, "Supplies on controller should be restored");
+}
+
+#[test]
+fn scenario_supply_balance_of_failure(){
+    let (dtoken, controller, utoken, uroot, user) = failure_fixture();
+    set_failure_config(&utoken, &uroot, FailureConfig { fail_balance_of: true, ..Default::default() });
+
+    call!(
+        user,
+        utoken.ft_transfer_call(dtoken.account_id(), U128(20), None, json!({"action": "SUPPLY"}).to_string()),
+        deposit = 1
+    );
+    set_failure_config(&utoken, &uroot, FailureConfig::default());
+
+    assert_eq!(view_utoken_balance(&utoken, user.account_id()), 20.to_string(), "Supplied tokens should be refunded");
+
+    let dtoken_balance: U128 = view!(dtoken.ft_balance_of(user.account_id())).unwrap_json();
+    assert_eq!(dtoken_balance, U128(0), "Dtokens shouldn't be minted");
+
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 0, "Supplies on controller shouldn't be increased");
+}
+
+#[test]
+fn scenario_supply_with_transfer_fee(){
+    let (dtoken, controller, utoken, uroot, user) = failure_fixture();
+    set_failure_config(&utoken, &uroot, FailureConfig { transfer_fee: U128(1000), ..Default::default() });
+
+    call!(
+        user,
+        utoken.ft_transfer_call(dtoken.account_id(), U128(20), None, json!({"action": "SUPPLY"}).to_string()),
+        deposit = 1
+    ).assert_success();
+
+    // 10% of the transfer goes to the utoken owner, the market supplies what it received
+    let user_balance: u128 = view_balance(&controller, Supply, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 18, "Supplies on controller should be 18");
+
+    let dtoken_balance: U128 = view!(dtoken.ft_balance_of(user.account_id())).unwrap_json();
+    assert_eq!(dtoken_balance, U128(18), "User should receive 18 dtokens");
+    assert_eq!(view_utoken_balance(&utoken, dtoken.account_id()), 38.to_string(), "Dtoken utoken balance should be 38");
+}
+
+#[test]
+fn scenario_borrow_with_burn(){
+    let (dtoken, controller, utoken, uroot, user) = failure_fixture();
+    set_failure_config(&utoken, &uroot, FailureConfig { burn_rate: U128(5000), ..Default::default() });
+
+    call!(
+        user,
+        dtoken.borrow(U128(10)),
+        deposit = 0
+    ).assert_success();
+
+    // Borrower owes the full amount while receiving only the part which wasn't burned
+    let user_balance: u128 = view_balance(&controller, Borrow, user.account_id(), dtoken.account_id());
+    assert_eq!(user_balance, 10, "Borrow on controller should be 10");
+    assert_eq!(view_utoken_balance(&utoken, user.account_id()), 25.to_string(), "User utoken balance should be 25");
+    assert_eq!(view_utoken_balance(&utoken, dtoken.account_id()), 10.to_string(), "Dtoken utoken balance should be 10");
+}
+
+fn deploy_market(root: &UserAccount, controller: &ContractAccount<controller::ContractContract>, name: &str) -> (ContractAccount<test_utoken::ContractContract>, ContractAccount<dtoken::ContractContract>) {
+    let utoken = deploy!(
+        contract: test_utoken::ContractContract,
+        contract_id: AccountId::new_unchecked(format!("{}_utoken", name)),
+        bytes: utoken_wasm_bytes(),
+        signer_account: root
+    );
+    call!(
+        root,
+        utoken.new_default_meta(root.account_id(), U128(0)),
+        deposit = 0
+    ).assert_success();
+
+    let dtoken = deploy!(
+        contract: dtoken::ContractContract,
+        contract_id: AccountId::new_unchecked(format!("{}_dtoken", name)),
+        bytes: dtoken_wasm_bytes(),
+        signer_account: root
+    );
+    call!(
+        root,
+        dtoken.new(
+            dConfig{
+                initial_exchange_rate: U128(1),
+                underlying_token_id: utoken.account_id(),
+                owner_id: root.account_id(),
+                controller_account_id: controller.account_id(),
+            }),
+        deposit = 0
+    ).assert_success();
+
+    call!(
+        root,
+        utoken.mint(dtoken.account_id(), U128(0)),
+        deposit = 0
+    ).assert_success();
+
+    list_market(root, controller, dtoken.account_id());
+
+    (utoken, dtoken)
+}
+
+fn oracle_fixture() -> (UserAccount, ContractAccount<test_oracle::ContractContract>, ContractAccount<controller::ContractContract>) {
+    let root = init_simulator(None);
+    let oracle_account = AccountId::new_unchecked("oracle_contract".to_string());
+
+    let controller = deploy!(
+        contract: controller::ContractContract,
+        contract_id: AccountId::new_unchecked("controller_contract".to_string()),
+        bytes: controller_wasm_bytes(),
+        signer_account: root
+    );
+    call!(
+        root,
+        controller.new_with_governance_delay(cConfig::new(root.account_id(), oracle_account.clone()), 0),
+        deposit = 0
+    ).assert_success();
+
+    let oracle = deploy!(
+        contract: test_oracle::ContractContract,
+        contract_id: oracle_account,
+        bytes: oracle_wasm_bytes(),
+        signer_account: root
+    );
+    call!(
+        root,
+        oracle.new(root.account_id(), controller.account_id()),
+        deposit = 0
+    ).assert_success();
+
+    (root, oracle, controller)
+}
+
+#[test]
+fn scenario_oracle_pushes_prices(){
+    let (root, oracle, controller) = oracle_fixture();
+    let asset_id = AccountId::new_unchecked("wnear_dtoken".to_string());
+    list_market(&root, &controller, asset_id.clone());
+
+    call!(
+        root,
+        oracle.set_prices(vec![Price { asset_id: asset_id.clone(), value: 10, volatility: 90 }]),
+        deposit = 0
+    ).assert_success();
+
+    let price: PriceInfo = view!(controller.get_price(asset_id.clone())).unwrap_json();
+    assert_eq!(price.value, U128(10), "Controller price should be pushed by oracle");
+    assert!(price.last_update.is_some(), "Price update should be recorded");
+
+    let result = call!(
+        root,
+        controller.oracle_on_data(PriceJsonList {
+            block_height: 1,
+            price_list: vec![Price { asset_id, value: 1, volatility: 90 }]
+        }),
+        deposit = 0
+    );
+    assert_failure(result, "doesn't match to the signer");
+}
+
+#[test]
+fn scenario_liquidation_after_price_drop(){
+    let (root, oracle, controller) = oracle_fixture();
+    let (wnear, dwnear) = deploy_market(&root, &controller, "wnear");
+    let (usdt, dusdt) = deploy_market(&root, &controller, "usdt");
+
+    let borrower = root.create_user("borrower".parse().unwrap(), to_yocto("100"));
+    let liquidator = root.create_user("liquidator".parse().unwrap(), to_yocto("100"));
+    for user in [&borrower, &liquidator] {
+        register_account(&dwnear, &controller, user);
+        call!(user, dusdt.storage_deposit(None, None), deposit = to_yocto("0.1")).assert_success();
+    }
+
+    call!(root, wnear.mint(borrower.account_id(), U128(100)), deposit = 0).assert_success();
+    call!(root, usdt.mint(dusdt.account_id(), U128(100)), deposit = 0).assert_success();
+    call!(root, usdt.mint(liquidator.account_id(), U128(100)), deposit = 0).assert_success();
+    call!(root, usdt.mint(borrower.account_id(), U128(0)), deposit = 0).assert_success();
+
+    call!(
+        root,
+        oracle.set_prices(vec![
+            Price { asset_id: dwnear.account_id(), value: 10, volatility: 100 },
+            Price { asset_id: dusdt.account_id(), value: 10, volatility: 100 },
+        ]),
+        deposit = 0
+    ).assert_success();
+
+    call!(
+        borrower,
+        wnear.ft_transfer_call(dwnear.account_id(), U128(100), None, json!({"action": "SUPPLY"}).to_string()),
+        deposit = 1
+    ).assert_success();
+    call!(
+        borrower,
+        dusdt.borrow(U128(80)),
+        deposit = 0
+    ).assert_success();
+
+    // Health factor is 125% until wNEAR price goes from 10 to 6
+    call!(
+        root,
+        oracle.set_price_path(dwnear.account_id(), PricePath { values: vec![U128(8), U128(6)], volatility: 100 }),
+        deposit = 0
+    ).assert_success();
+    for _ in 0..2 {
+        call!(root, oracle.advance_price_paths(), deposit = 0).assert_success();
+    }
+
+    let position: AccountPosition = view!(controller.get_account_position(borrower.account_id())).unwrap_json();
+    assert_eq!(position.health_factor, U128(7500), "Health factor should drop to 75%");
+
+    call!(
+        liquidator,
+        usdt.ft_transfer_call(
+            dusdt.account_id(),
+            U128(60),
+            None,
+            json!({
+                "action": "LIQUIDATION",
+                "memo": {"borrower": borrower.account_id(), "collateral_dtoken": dwnear.account_id()}
+            }).to_string()
+        ),
+        deposit = 1
+    ).assert_success();
+
+    // Close factor limits repay to 40, discount is 12.5%, so 40 * 10 / 0.875 / 6 = 76 wNEAR is seized and 3% of it is fee
+    let borrow: u128 = view_balance(&controller, Borrow, borrower.account_id(), dusdt.account_id());
+    assert_eq!(borrow, 40, "Half of the borrow should be repaid");
+
+    let liquidator_supply: u128 = view_balance(&controller, Supply, liquidator.account_id(), dwnear.account_id());
+    assert_eq!(liquidator_supply, 74, "Liquidator should receive seized collateral without the fee");
+
+    let borrower_supply: u128 = view_balance(&controller, Supply, borrower.account_id(), dwnear.account_id());
+    assert_eq!(borrower_supply, 24, "Seized collateral should be taken from the borrower");
+
+    let liquidator_dtokens: U128 = view!(dwnear.ft_balance_of(liquidator.account_id())).unwrap_json();
+    assert_eq!(liquidator_dtokens, U128(74), "Liquidator should receive dtokens");
+
+    let liquidator_balance: String = view!(usdt.ft_balance_of(liquidator.account_id())).unwrap_json();
+    assert_eq!(liquidator_balance, 60.to_string(), "Amount above close factor should be refunded");
+}
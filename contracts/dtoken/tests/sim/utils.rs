@@ -11,6 +11,9 @@ near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
     DTOKEN_WASM_BYTES => "../../res/dtoken.wasm",
     UTOKEN_WASM_BYTES => "../../res/test_utoken.wasm",
     CONTROLLER_WASM_BYTES => "../../res/controller.wasm",
+    ORACLE_WASM_BYTES => "../../res/test_oracle.wasm",
+    DTOKEN_BASELINE_WASM_BYTES => "../../res/baseline/dtoken.wasm",
+    CONTROLLER_BASELINE_WASM_BYTES => "../../res/baseline/controller.wasm",
 }
 
 pub fn init_dtoken(
@@ -73,4 +76,27 @@ pub fn init_controller(
     );
 
     (root, contract, user_account)
-}
\ No newline at end of file
+}
+pub fn dtoken_wasm_bytes() -> &'static [u8] {
+    &DTOKEN_WASM_BYTES
+}
+
+pub fn controller_wasm_bytes() -> &'static [u8] {
+    &CONTROLLER_WASM_BYTES
+}
+
+pub fn utoken_wasm_bytes() -> &'static [u8] {
+    &UTOKEN_WASM_BYTES
+}
+
+pub fn oracle_wasm_bytes() -> &'static [u8] {
+    &ORACLE_WASM_BYTES
+}
+
+pub fn dtoken_baseline_wasm_bytes() -> &'static [u8] {
+    &DTOKEN_BASELINE_WASM_BYTES
+}
+
+pub fn controller_baseline_wasm_bytes() -> &'static [u8] {
+    &CONTROLLER_BASELINE_WASM_BYTES
+}
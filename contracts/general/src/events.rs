@@ -0,0 +1,328 @@
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json;
+use near_sdk::{env, AccountId, BlockHeight};
+
+use crate::{Percent, Price, WBalance};
+
+/// NEP-297 standard name used by all Nearlend contracts
+pub const EVENT_STANDARD: &str = "nearlend";
+
+/// Version of the Nearlend events format
+pub const EVENT_VERSION: &str = "1.0.0";
+
+const EVENT_JSON_PREFIX: &str = "EVENT_JSON:";
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SupplyData {
+    pub account_id: AccountId,
+    pub amount: WBalance,
+    pub dtoken_amount: WBalance,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WithdrawData {
+    pub account_id: AccountId,
+    pub amount: WBalance,
+    pub dtoken_amount: WBalance,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BorrowData {
+    pub account_id: AccountId,
+    pub amount: WBalance,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RepayData {
+    pub account_id: AccountId,
+    pub amount: WBalance,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LiquidateData {
+    pub liquidator_id: AccountId,
+    pub borrower_id: AccountId,
+    pub borrowing_market: AccountId,
+    pub collateral_market: AccountId,
+    pub repaid_amount: WBalance,
+    pub seized_amount: WBalance,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccrueInterestData {
+    pub interest_accumulated: WBalance,
+    pub borrow_index: WBalance,
+    pub total_borrows: WBalance,
+    pub total_reserves: WBalance,
+    pub block_height: BlockHeight,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceUpdateData {
+    pub asset_id: AccountId,
+    pub value: WBalance,
+    pub volatility: Percent,
+    pub block_height: BlockHeight,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceRejectedData {
+    pub asset_id: AccountId,
+    pub value: WBalance,
+    pub volatility: Percent,
+    pub block_height: BlockHeight,
+    pub reason: serde_json::Value,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FlashLoanData {
+    pub initiator_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount: WBalance,
+    pub fee: WBalance,
+    pub repaid: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BadDebtWriteOffData {
+    pub account_id: AccountId,
+    pub market: AccountId,
+    pub amount: WBalance,
+    pub covered_by_reserves: WBalance,
+    pub socialized: WBalance,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GovernanceData {
+    pub proposal_id: u64,
+    pub action: serde_json::Value,
+    pub eta: BlockHeight,
+    pub status: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConfigChangeData {
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+/// Every event kind emitted by the protocol, serialized as `"event"` and `"data"` fields
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Supply(Vec<SupplyData>),
+    Withdraw(Vec<WithdrawData>),
+    Borrow(Vec<BorrowData>),
+    Repay(Vec<RepayData>),
+    Liquidate(Vec<LiquidateData>),
+    AccrueInterest(Vec<AccrueInterestData>),
+    PriceUpdate(Vec<PriceUpdateData>),
+    PriceRejected(Vec<PriceRejectedData>),
+    FlashLoan(Vec<FlashLoanData>),
+    BadDebtWriteOff(Vec<BadDebtWriteOffData>),
+    Governance(Vec<GovernanceData>),
+    ConfigChange(Vec<ConfigChangeData>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a EventKind,
+}
+
+impl EventKind {
+    pub fn to_event_string(&self) -> String {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_VERSION,
+            event: self,
+        };
+        format!(
+            "{}{}",
+            EVENT_JSON_PREFIX,
+            serde_json::to_string(&log).unwrap_or_else(|_| env::abort())
+        )
+    }
+
+    /// Logs the event in NEP-297 format
+    pub fn emit(&self) {
+        env::log_str(&self.to_event_string());
+    }
+}
+
+/// Shortcuts for emitting a single event of each kind
+pub mod emit {
+    use super::*;
+    use near_sdk::Balance;
+
+    pub fn supply(account_id: &AccountId, amount: Balance, dtoken_amount: Balance) {
+        EventKind::Supply(vec![SupplyData {
+            account_id: account_id.clone(),
+            amount: amount.into(),
+            dtoken_amount: dtoken_amount.into(),
+        }])
+        .emit();
+    }
+
+    pub fn withdraw(account_id: &AccountId, amount: Balance, dtoken_amount: Balance) {
+        EventKind::Withdraw(vec![WithdrawData {
+            account_id: account_id.clone(),
+            amount: amount.into(),
+            dtoken_amount: dtoken_amount.into(),
+        }])
+        .emit();
+    }
+
+    pub fn borrow(account_id: &AccountId, amount: Balance) {
+        EventKind::Borrow(vec![BorrowData {
+            account_id: account_id.clone(),
+            amount: amount.into(),
+        }])
+        .emit();
+    }
+
+    pub fn repay(account_id: &AccountId, amount: Balance) {
+        EventKind::Repay(vec![RepayData {
+            account_id: account_id.clone(),
+            amount: amount.into(),
+        }])
+        .emit();
+    }
+
+    pub fn liquidate(
+        liquidator_id: &AccountId,
+        borrower_id: &AccountId,
+        borrowing_market: &AccountId,
+        collateral_market: &AccountId,
+        repaid_amount: Balance,
+        seized_amount: Balance,
+    ) {
+        EventKind::Liquidate(vec![LiquidateData {
+            liquidator_id: liquidator_id.clone(),
+            borrower_id: borrower_id.clone(),
+            borrowing_market: borrowing_market.clone(),
+            collateral_market: collateral_market.clone(),
+            repaid_amount: repaid_amount.into(),
+            seized_amount: seized_amount.into(),
+        }])
+        .emit();
+    }
+
+    pub fn accrue_interest(
+        interest_accumulated: Balance,
+        borrow_index: Balance,
+        total_borrows: Balance,
+        total_reserves: Balance,
+    ) {
+        EventKind::AccrueInterest(vec![AccrueInterestData {
+            interest_accumulated: interest_accumulated.into(),
+            borrow_index: borrow_index.into(),
+            total_borrows: total_borrows.into(),
+            total_reserves: total_reserves.into(),
+            block_height: env::block_height(),
+        }])
+        .emit();
+    }
+
+    pub fn price_update(asset_id: &AccountId, value: Balance, volatility: Percent, block_height: BlockHeight) {
+        EventKind::PriceUpdate(vec![PriceUpdateData {
+            asset_id: asset_id.clone(),
+            value: value.into(),
+            volatility,
+            block_height,
+        }])
+        .emit();
+    }
+
+    pub fn price_rejected<T: Serialize>(price: &Price, block_height: BlockHeight, reason: &T) {
+        EventKind::PriceRejected(vec![PriceRejectedData {
+            asset_id: price.asset_id.clone(),
+            value: price.value.into(),
+            volatility: price.volatility,
+            block_height,
+            reason: serde_json::to_value(reason).unwrap_or_else(|_| env::abort()),
+        }])
+        .emit();
+    }
+
+    pub fn flash_loan(initiator_id: &AccountId, receiver_id: &AccountId, amount: Balance, fee: Balance, repaid: bool) {
+        EventKind::FlashLoan(vec![FlashLoanData {
+            initiator_id: initiator_id.clone(),
+            receiver_id: receiver_id.clone(),
+            amount: amount.into(),
+            fee: fee.into(),
+            repaid,
+        }])
+        .emit();
+    }
+
+    pub fn bad_debt_write_off(
+        account_id: &AccountId,
+        market: &AccountId,
+        amount: Balance,
+        covered_by_reserves: Balance,
+        socialized: Balance,
+    ) {
+        EventKind::BadDebtWriteOff(vec![BadDebtWriteOffData {
+            account_id: account_id.clone(),
+            market: market.clone(),
+            amount: amount.into(),
+            covered_by_reserves: covered_by_reserves.into(),
+            socialized: socialized.into(),
+        }])
+        .emit();
+    }
+
+    pub fn governance<T: Serialize>(proposal_id: u64, action: &T, eta: BlockHeight, status: &str) {
+        EventKind::Governance(vec![GovernanceData {
+            proposal_id,
+            action: serde_json::to_value(action).unwrap_or_else(|_| env::abort()),
+            eta,
+            status: status.to_string(),
+        }])
+        .emit();
+    }
+
+    pub fn config_change<T: Serialize>(key: &str, value: &T) {
+        EventKind::ConfigChange(vec![ConfigChangeData {
+            key: key.to_string(),
+            value: serde_json::to_value(value).unwrap_or_else(|_| env::abort()),
+        }])
+        .emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_format() {
+        let event = EventKind::Borrow(vec![BorrowData {
+            account_id: AccountId::new_unchecked("alice.near".to_string()),
+            amount: WBalance::from(10),
+        }]);
+
+        assert_eq!(
+            event.to_event_string(),
+            r#"EVENT_JSON:{"standard":"nearlend","version":"1.0.0","event":"borrow","data":[{"account_id":"alice.near","amount":"10"}]}"#
+        );
+    }
+}
@@ -3,10 +3,12 @@ use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::json_types::U128;
 use near_sdk::{AccountId, Balance, Gas};
 
+pub mod events;
+
 pub const NO_DEPOSIT: Balance = 0;
 pub const ONE_YOCTO: Balance = 1;
 pub const TGAS: Gas = near_sdk::Gas::ONE_TERA;
-pub const RATIO_DECIMALS: u128 = 10u128.pow(4);
+pub use math::RATIO_DECIMALS;
 
 pub type WBalance = U128;
 
@@ -18,7 +20,7 @@ pub type WPercent = U128;
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Price {
     /// Asset Id
     pub asset_id: AccountId,
@@ -29,3 +31,69 @@ pub struct Price {
     /// Asset volatility value
     pub volatility: Percent // 0..100%
 }
+
+/// Prices pushed by an oracle to the controller
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceJsonList {
+    /// Block number
+    pub block_height: u64,
+
+    /// Vector of asset prices
+    pub price_list: Vec<Price>,
+}
+
+/// Outcome of a liquidation approved by the controller
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LiquidationResult {
+    /// Amount of borrowed tokens repaid by the liquidator
+    pub repaid_amount: WBalance,
+
+    /// Amount of collateral tokens taken from the borrower, including the protocol fee
+    pub seized_amount: WBalance,
+
+    /// Part of seized collateral credited to reserves of the collateral market
+    pub protocol_fee: WBalance,
+}
+
+/// Parameters of the market borrow rate, set on the market by controller governance
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InterestRateModel {
+    /// Minimum annual borrow rate
+    pub base_rate: WRatio,
+
+    /// Annual rate increase with respect to utilization before the kink
+    pub multiplier: WRatio,
+
+    /// Utilization point at which the jump multiplier is applied
+    pub kink: WRatio,
+
+    /// Annual rate increase with respect to utilization after the kink
+    pub jump_multiplier: WRatio,
+
+    /// Part of the accrued interest which goes to reserves
+    pub reserve_factor: WRatio,
+}
+
+impl Default for InterestRateModel {
+    fn default() -> Self {
+        Self {
+            base_rate: U128(0),
+            multiplier: U128(500),
+            kink: U128(8000),
+            jump_multiplier: U128(10900),
+            reserve_factor: U128(0),
+        }
+    }
+}
+
+impl InterestRateModel {
+    pub fn assert_valid(&self) {
+        assert!(
+            Ratio::from(self.kink) <= RATIO_DECIMALS && Ratio::from(self.reserve_factor) <= RATIO_DECIMALS,
+            "Kink and reserve factor should be less or equal to 100%"
+        );
+    }
+}
@@ -0,0 +1,27 @@
+/// Exchange rate between dtokens and underlying tokens held by the market,
+/// the initial rate is used until the first dtokens are minted
+pub fn exchange_rate(underlying_balance: u128, total_borrows: u128, total_reserves: u128, total_supply: u128, initial_exchange_rate: u128) -> u128 {
+    if total_supply == 0 {
+        return initial_exchange_rate;
+    }
+    (underlying_balance + total_borrows - total_reserves) / total_supply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn initial_rate_without_supply(balance in 0u128..10u128.pow(30), borrows in 0u128..10u128.pow(30), initial in 1u128..10u128.pow(6)) {
+            prop_assert_eq!(exchange_rate(balance, borrows, 0, 0, initial), initial);
+        }
+
+        #[test]
+        fn reserves_decrease_rate(balance in 0u128..10u128.pow(30), borrows in 0u128..10u128.pow(30), reserves in 0u128..10u128.pow(20), supply in 1u128..10u128.pow(30)) {
+            prop_assume!(reserves <= balance);
+            prop_assert!(exchange_rate(balance, borrows, reserves, supply, 1) <= exchange_rate(balance, borrows, 0, supply, 1));
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::RATIO_DECIMALS;
+
+/// Value of the balance reduced by asset volatility, volatility is a percent from 0 to 100
+pub fn volatility_adjusted_value(balance: u128, price: u128, volatility: u128) -> u128 {
+    price * balance * volatility / 100
+}
+
+/// Ratio of adjusted collaterals to adjusted borrows, RATIO_DECIMALS is 100%.
+/// Accounts without borrows have health factor of 100%.
+pub fn health_factor(collaterals: u128, borrows: u128) -> u128 {
+    if borrows == 0 {
+        return RATIO_DECIMALS;
+    }
+    collaterals * RATIO_DECIMALS / borrows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn adjusted_value_is_at_most_value(balance in 0u128..10u128.pow(24), price in 0u128..10u128.pow(8), volatility in 0u128..=100) {
+            prop_assert!(volatility_adjusted_value(balance, price, volatility) <= balance * price);
+        }
+
+        #[test]
+        fn more_collateral_is_healthier(collaterals in 0u128..10u128.pow(30), extra in 0u128..10u128.pow(30), borrows in 1u128..10u128.pow(30)) {
+            prop_assert!(health_factor(collaterals, borrows) <= health_factor(collaterals + extra, borrows));
+        }
+
+        #[test]
+        fn covered_borrows_are_healthy(borrows in 1u128..10u128.pow(30), extra in 0u128..10u128.pow(30)) {
+            prop_assert!(health_factor(borrows + extra, borrows) >= RATIO_DECIMALS);
+        }
+    }
+}
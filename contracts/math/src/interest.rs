@@ -0,0 +1,121 @@
+use crate::RATIO_DECIMALS;
+
+/// Approximate number of blocks produced per year, one block per second
+pub const BLOCKS_PER_YEAR: u128 = 31_536_000;
+
+/// Borrow index value at the moment of market creation
+pub const INITIAL_BORROW_INDEX: u128 = 10u128.pow(18);
+
+/// Share of available liquidity which is borrowed, RATIO_DECIMALS is 100%
+pub fn utilization_rate(cash: u128, borrows: u128, reserves: u128) -> u128 {
+    let total = (cash + borrows).saturating_sub(reserves);
+    if borrows == 0 || total == 0 {
+        return 0;
+    }
+    borrows * RATIO_DECIMALS / total
+}
+
+/// Annual borrow rate of the jump rate model, RATIO_DECIMALS is 100%
+pub fn borrow_rate(utilization: u128, base_rate: u128, multiplier: u128, kink: u128, jump_multiplier: u128) -> u128 {
+    if utilization <= kink {
+        return base_rate + multiplier * utilization / RATIO_DECIMALS;
+    }
+
+    base_rate + multiplier * kink / RATIO_DECIMALS + jump_multiplier * (utilization - kink) / RATIO_DECIMALS
+}
+
+/// Annual supply rate, the part of borrow rate which isn't taken to reserves spread among all liquidity
+pub fn supply_rate(utilization: u128, borrow_rate: u128, reserve_factor: u128) -> u128 {
+    let rate_to_pool = borrow_rate * (RATIO_DECIMALS - reserve_factor) / RATIO_DECIMALS;
+    rate_to_pool * utilization / RATIO_DECIMALS
+}
+
+/// Multiplies amount by a factor with INITIAL_BORROW_INDEX precision avoiding u128 overflow
+pub fn apply_index_factor(amount: u128, factor: u128) -> u128 {
+    amount / INITIAL_BORROW_INDEX * factor + amount % INITIAL_BORROW_INDEX * factor / INITIAL_BORROW_INDEX
+}
+
+/// Interest accrued per unit over the number of blocks with INITIAL_BORROW_INDEX precision
+pub fn interest_factor(borrow_rate: u128, blocks: u128) -> u128 {
+    borrow_rate * blocks * INITIAL_BORROW_INDEX / (RATIO_DECIMALS * BLOCKS_PER_YEAR)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Accrual {
+    /// Interest added to total borrows
+    pub interest_accumulated: u128,
+
+    /// Part of the interest added to total reserves
+    pub reserves_accumulated: u128,
+
+    /// Borrow index after the accrual
+    pub borrow_index: u128,
+}
+
+/// Interest accrued on total borrows over the number of blocks
+pub fn accrue_interest(total_borrows: u128, borrow_index: u128, borrow_rate: u128, reserve_factor: u128, blocks: u128) -> Accrual {
+    let factor = interest_factor(borrow_rate, blocks);
+    let interest_accumulated = apply_index_factor(total_borrows, factor);
+
+    Accrual {
+        interest_accumulated,
+        reserves_accumulated: interest_accumulated * reserve_factor / RATIO_DECIMALS,
+        borrow_index: borrow_index + apply_index_factor(borrow_index, factor),
+    }
+}
+
+/// Borrow with interest accrued since the account index was recorded
+pub fn borrow_balance(principal: u128, borrow_index: u128, account_index: u128) -> u128 {
+    if account_index == 0 {
+        return principal;
+    }
+    apply_index_factor(principal, borrow_index * INITIAL_BORROW_INDEX / account_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_borrow_rate_kink() {
+        assert_eq!(borrow_rate(8000, 0, 500, 8000, 10900), 400);
+        assert_eq!(borrow_rate(9000, 0, 500, 8000, 10900), 400 + 1090);
+    }
+
+    proptest! {
+        #[test]
+        fn utilization_is_at_most_100_percent(cash in 0u128..10u128.pow(30), borrows in 0u128..10u128.pow(30), reserves in 0u128..10u128.pow(30)) {
+            prop_assume!(reserves <= cash);
+            prop_assert!(utilization_rate(cash, borrows, reserves) <= RATIO_DECIMALS);
+        }
+
+        #[test]
+        fn borrow_rate_is_monotonic(utilization in 0u128..RATIO_DECIMALS, kink in 0u128..=RATIO_DECIMALS) {
+            prop_assert!(borrow_rate(utilization, 200, 500, kink, 10900) <= borrow_rate(utilization + 1, 200, 500, kink, 10900));
+        }
+
+        #[test]
+        fn supply_rate_is_below_borrow_rate(utilization in 0u128..=RATIO_DECIMALS, rate in 0u128..100 * RATIO_DECIMALS, reserve_factor in 0u128..=RATIO_DECIMALS) {
+            prop_assert!(supply_rate(utilization, rate, reserve_factor) <= rate);
+        }
+
+        #[test]
+        fn apply_index_factor_matches_wide_math(amount in 0u128..10u128.pow(30), factor in 0u128..2 * INITIAL_BORROW_INDEX) {
+            let expected = crate::u256::U256::from(amount) * crate::u256::U256::from(factor) / crate::u256::U256::from(INITIAL_BORROW_INDEX);
+            prop_assert_eq!(apply_index_factor(amount, factor), expected.as_u128());
+        }
+
+        #[test]
+        fn accrual_never_decreases_index(total_borrows in 0u128..10u128.pow(30), rate in 0u128..100 * RATIO_DECIMALS, reserve_factor in 0u128..=RATIO_DECIMALS, blocks in 0u128..BLOCKS_PER_YEAR) {
+            let accrual = accrue_interest(total_borrows, INITIAL_BORROW_INDEX, rate, reserve_factor, blocks);
+            prop_assert!(accrual.borrow_index >= INITIAL_BORROW_INDEX);
+            prop_assert!(accrual.reserves_accumulated <= accrual.interest_accumulated);
+        }
+
+        #[test]
+        fn borrow_balance_grows_with_index(principal in 0u128..10u128.pow(30), growth in 0u128..INITIAL_BORROW_INDEX) {
+            prop_assert!(borrow_balance(principal, INITIAL_BORROW_INDEX + growth, INITIAL_BORROW_INDEX) >= principal);
+        }
+    }
+}
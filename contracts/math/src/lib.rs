@@ -0,0 +1,19 @@
+//! Pure protocol math shared by the contracts and off-chain tools.
+//! Functions don't touch contract storage, all amounts and ratios are plain integers.
+#![cfg_attr(not(test), no_std)]
+
+pub mod exchange_rate;
+pub mod health;
+pub mod interest;
+pub mod liquidation;
+pub mod rewards;
+mod u256;
+
+pub use crate::exchange_rate::*;
+pub use crate::health::*;
+pub use crate::interest::*;
+pub use crate::liquidation::*;
+pub use crate::rewards::*;
+
+/// Precision of ratios, 10000 is 100%
+pub const RATIO_DECIMALS: u128 = 10u128.pow(4);
@@ -0,0 +1,60 @@
+use crate::u256::U256;
+use crate::RATIO_DECIMALS;
+
+/// Discount of seized collateral, (1 - health factor) / 2 limited by floor and cap
+pub fn liquidation_discount(health_factor: u128, floor: u128, cap: u128) -> u128 {
+    let discount = RATIO_DECIMALS.saturating_sub(health_factor) / 2;
+    discount.max(floor).min(cap)
+}
+
+/// The most of a borrow which can be repaid by a single liquidation
+pub fn max_repay_amount(borrow_amount: u128, max_close_factor: u128) -> u128 {
+    borrow_amount * max_close_factor / RATIO_DECIMALS
+}
+
+/// Amount of collateral tokens worth the repaid amount of borrowed tokens with the discount applied,
+/// discount has to be less than 100%
+pub fn seize_amount(repaid_amount: u128, borrow_price: u128, collateral_price: u128, discount: u128) -> u128 {
+    (U256::from(repaid_amount) * U256::from(borrow_price) * U256::from(RATIO_DECIMALS)
+        / U256::from(RATIO_DECIMALS - discount)
+        / U256::from(collateral_price))
+    .as_u128()
+}
+
+/// Part of seized collateral which goes to reserves
+pub fn liquidation_fee(seized_amount: u128, fee: u128) -> u128 {
+    seized_amount * fee / RATIO_DECIMALS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn discount_is_within_limits(health_factor in 0u128..2 * RATIO_DECIMALS, floor in 0u128..RATIO_DECIMALS, extra in 0u128..RATIO_DECIMALS) {
+            let cap = std::cmp::min(floor + extra, RATIO_DECIMALS - 1);
+            prop_assume!(floor <= cap);
+            let discount = liquidation_discount(health_factor, floor, cap);
+            prop_assert!(discount >= floor && discount <= cap);
+        }
+
+        #[test]
+        fn seized_value_covers_repaid_value(repaid in 1u128..10u128.pow(24), borrow_price in 1u128..10u128.pow(6), collateral_price in 1u128..10u128.pow(6), discount in 0u128..RATIO_DECIMALS) {
+            let seized = seize_amount(repaid, borrow_price, collateral_price, discount);
+            // Liquidator never gets less than the repaid value, up to rounding of one collateral token
+            prop_assert!((seized + 1) * collateral_price >= repaid * borrow_price);
+        }
+
+        #[test]
+        fn repay_is_limited_by_close_factor(borrow in 0u128..10u128.pow(30), close_factor in 0u128..=RATIO_DECIMALS) {
+            prop_assert!(max_repay_amount(borrow, close_factor) <= borrow);
+        }
+
+        #[test]
+        fn fee_is_part_of_seized(seized in 0u128..10u128.pow(30), fee in 0u128..=RATIO_DECIMALS) {
+            prop_assert!(liquidation_fee(seized, fee) <= seized);
+        }
+    }
+}
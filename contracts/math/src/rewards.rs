@@ -0,0 +1,32 @@
+use crate::u256::U256;
+
+/// Precision of reward indexes, index grows by rewards per a unit of position
+pub const REWARD_INDEX_DECIMALS: u128 = 10u128.pow(18);
+
+/// Growth of the reward index after emitting rewards for the number of blocks among the total position
+pub fn reward_index_increase(emission_per_block: u128, blocks: u64, total: u128) -> u128 {
+    if blocks == 0 || total == 0 || emission_per_block == 0 {
+        return 0;
+    }
+    (U256::from(emission_per_block) * U256::from(blocks) * U256::from(REWARD_INDEX_DECIMALS) / U256::from(total)).as_u128()
+}
+
+/// Rewards accrued on the balance since the account index was recorded
+pub fn rewards_amount(balance: u128, index: u128, account_index: u128) -> u128 {
+    (U256::from(balance) * U256::from(index.saturating_sub(account_index)) / U256::from(REWARD_INDEX_DECIMALS)).as_u128()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn distributed_rewards_do_not_exceed_emission(emission in 0u128..10u128.pow(24), blocks in 0u64..10u64.pow(8), total in 10u128.pow(18)..10u128.pow(30), share in 0u128..=100) {
+            let balance = total * share / 100;
+            let increase = reward_index_increase(emission, blocks, total);
+            prop_assert!(rewards_amount(balance, increase, 0) <= emission * blocks as u128);
+        }
+    }
+}
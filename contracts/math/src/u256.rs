@@ -0,0 +1,5 @@
+#![allow(clippy::all)]
+
+uint::construct_uint! {
+    pub struct U256(4);
+}
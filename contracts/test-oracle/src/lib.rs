@@ -0,0 +1,193 @@
+use near_sdk::{AccountId, BorshStorageKey, env, ext_contract, near_bindgen, PanicOnDefault, Promise};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use general::*;
+
+#[derive(BorshSerialize, BorshStorageKey)]
+enum StorageKeys {
+    Prices,
+    PricePaths,
+}
+
+/// Scripted prices of an asset, one value is applied per advance_price_paths call
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PricePath {
+    /// Prices left to apply, the first one goes next
+    pub values: Vec<WBalance>,
+
+    /// Volatility of all prices of the path, 0..100%
+    pub volatility: Percent,
+}
+
+#[ext_contract(ext_controller)]
+trait ControllerInterface {
+    fn oracle_on_data(&mut self, price_data: PriceJsonList);
+}
+
+/// Mock of a price oracle which pushes prices set by the owner to the controller
+#[near_bindgen]
+#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
+pub struct Contract {
+    owner_id: AccountId,
+    controller_account_id: AccountId,
+    prices: UnorderedMap<AccountId, Price>,
+    price_paths: UnorderedMap<AccountId, PricePath>,
+}
+
+impl Contract {
+    fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "This functionality is allowed to be called by admin only"
+        );
+    }
+
+    fn push_prices(&self, price_list: Vec<Price>) -> Promise {
+        ext_controller::oracle_on_data(
+            PriceJsonList { block_height: env::block_height(), price_list },
+            self.controller_account_id.clone(),
+            NO_DEPOSIT,
+            TGAS * 20,
+        )
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(owner_id: AccountId, controller_account_id: AccountId) -> Self {
+        assert!(!env::state_exists(), "Already initialized");
+        Self {
+            owner_id,
+            controller_account_id,
+            prices: UnorderedMap::new(StorageKeys::Prices),
+            price_paths: UnorderedMap::new(StorageKeys::PricePaths),
+        }
+    }
+
+    /// Stores prices and pushes them to the controller
+    pub fn set_prices(&mut self, prices: Vec<Price>) -> Promise {
+        self.assert_owner();
+        for price in prices.iter() {
+            self.prices.insert(&price.asset_id, price);
+        }
+        self.push_prices(prices)
+    }
+
+    /// Replaces the scripted prices of the asset, they are applied by advance_price_paths
+    pub fn set_price_path(&mut self, asset_id: AccountId, path: PricePath) {
+        self.assert_owner();
+        assert!(path.volatility <= 100, "Volatility should be less or equal to 100%");
+        self.price_paths.insert(&asset_id, &path);
+    }
+
+    /// Applies the next price of every price path and pushes changed prices to the controller,
+    /// finished paths are removed
+    pub fn advance_price_paths(&mut self) -> Promise {
+        self.assert_owner();
+
+        let mut price_list = Vec::new();
+        for (asset_id, mut path) in self.price_paths.to_vec() {
+            if path.values.is_empty() {
+                self.price_paths.remove(&asset_id);
+                continue;
+            }
+
+            let price = Price { asset_id: asset_id.clone(), value: path.values.remove(0).into(), volatility: path.volatility };
+            self.prices.insert(&asset_id, &price);
+            price_list.push(price);
+
+            if path.values.is_empty() {
+                self.price_paths.remove(&asset_id);
+            } else {
+                self.price_paths.insert(&asset_id, &path);
+            }
+        }
+
+        assert!(!price_list.is_empty(), "There are no price paths to advance");
+        self.push_prices(price_list)
+    }
+
+    /// Pushes all stored prices to the controller again
+    pub fn push_all_prices(&self) -> Promise {
+        self.assert_owner();
+        self.push_prices(self.prices.values().collect())
+    }
+
+    pub fn get_price(&self, asset_id: AccountId) -> Option<Price> {
+        self.prices.get(&asset_id)
+    }
+
+    pub fn get_prices(&self, asset_ids: Vec<AccountId>) -> Vec<Price> {
+        asset_ids.into_iter().filter_map(|asset_id| self.prices.get(&asset_id)).collect()
+    }
+
+    pub fn get_price_path(&self, asset_id: AccountId) -> Option<PricePath> {
+        self.price_paths.get(&asset_id)
+    }
+
+    pub fn get_controller_account_id(&self) -> AccountId {
+        self.controller_account_id.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    fn init() -> (Contract, AccountId) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(alice()).build());
+        let contract = Contract::new(alice(), bob());
+        (contract, "dwnear.near".parse().unwrap())
+    }
+
+    #[test]
+    fn test_set_prices() {
+        let (mut contract, asset_id) = init();
+
+        contract.set_prices(vec![Price { asset_id: asset_id.clone(), value: 10, volatility: 90 }]);
+        assert_eq!(contract.get_price(asset_id.clone()).unwrap().value, 10);
+        assert_eq!(contract.get_prices(vec![asset_id, carol()]).len(), 1);
+    }
+
+    #[test]
+    fn test_advance_price_paths() {
+        let (mut contract, asset_id) = init();
+
+        contract.set_price_path(asset_id.clone(), PricePath { values: vec![U128(10), U128(8)], volatility: 90 });
+
+        contract.advance_price_paths();
+        assert_eq!(contract.get_price(asset_id.clone()).unwrap().value, 10);
+        assert_eq!(contract.get_price_path(asset_id.clone()).unwrap().values, vec![U128(8)]);
+
+        contract.advance_price_paths();
+        assert_eq!(contract.get_price(asset_id.clone()).unwrap().value, 8);
+        assert!(contract.get_price_path(asset_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "There are no price paths to advance")]
+    fn failed_advance_without_paths() {
+        let (mut contract, _) = init();
+
+        contract.advance_price_paths();
+    }
+
+    #[test]
+    #[should_panic(expected = "This functionality is allowed to be called by admin only")]
+    fn failed_set_price_path_not_owner() {
+        let (mut contract, asset_id) = init();
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(bob()).build());
+        contract.set_price_path(asset_id, PricePath { values: vec![U128(10)], volatility: 90 });
+    }
+}
@@ -2,16 +2,61 @@ use near_contract_standards::fungible_token::FungibleToken;
 use near_contract_standards::fungible_token::metadata::{
     FungibleTokenMetadata, FungibleTokenMetadataProvider, FT_METADATA_SPEC,
 };
-use near_sdk::{AccountId, near_bindgen, PanicOnDefault, PromiseOrValue, env};
+use near_contract_standards::fungible_token::core::FungibleTokenCore;
+use near_contract_standards::fungible_token::resolver::FungibleTokenResolver;
+use near_sdk::{assert_one_yocto, AccountId, Balance, BlockHeight, Gas, near_bindgen, PanicOnDefault, Promise, PromiseOrValue, env};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::LazyOption;
+use near_sdk::collections::{LazyOption, LookupMap};
 use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// Precision of transfer fee and burn rate, 10000 is 100%
+pub const FAILURE_RATIO_DECIMALS: u128 = 10_000;
+
+const FAIL_TRANSFER_GAS: Gas = Gas(5_000_000_000_000);
+
+/// Whole tokens given by the faucet on initialization
+const DEFAULT_FAUCET_TOKENS: u128 = 1000;
+
+/// Blocks between faucet calls of an account on initialization, about a day of 1 second blocks
+const DEFAULT_FAUCET_PERIOD: BlockHeight = 24 * 60 * 60;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FaucetConfig {
+    /// Amount of tokens given per faucet call
+    pub amount: U128,
+
+    /// Number of blocks an account has to wait between faucet calls
+    pub period: BlockHeight,
+}
+
+/// Switches which make the token misbehave, so failure branches of markets can be tested
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FailureConfig {
+    /// Number of next ft_transfer and ft_transfer_call calls which fail without moving tokens
+    pub fail_transfers: u64,
+
+    /// ft_balance_of panics while set
+    pub fail_balance_of: bool,
+
+    /// Part of each transfer sent to the owner, FAILURE_RATIO_DECIMALS is 100%
+    pub transfer_fee: U128,
+
+    /// Part of each transfer burned, FAILURE_RATIO_DECIMALS is 100%
+    pub burn_rate: U128,
+}
 
 #[near_bindgen]
 #[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
 pub struct Contract {
     token: FungibleToken,
     metadata: LazyOption<FungibleTokenMetadata>,
+    owner_id: AccountId,
+    failure_config: FailureConfig,
+    faucet_config: FaucetConfig,
+    last_faucet_blocks: LookupMap<AccountId, BlockHeight>,
 }
 
 // example from near
@@ -22,18 +67,23 @@ const DATA_IMAGE_SVG_NEAR_ICON: &str = "data:image/svg+xml,%3Csvg xmlns='http://
 impl Contract {
     #[init]
     pub fn new_default_meta(owner_id: AccountId, total_supply: U128) -> Self {
+        Self::new_with_meta(owner_id, total_supply, "Test token".to_string(), "TEST".to_string(), 24)
+    }
+
+    /// Initializes the token with the default icon, e.g. "USD Coin", "USDC" and 6 decimals for a USDC stand-in
+    #[init]
+    pub fn new_with_meta(owner_id: AccountId, total_supply: U128, name: String, symbol: String, decimals: u8) -> Self {
         Self::new(
             owner_id,
             total_supply,
             FungibleTokenMetadata {
                 spec: FT_METADATA_SPEC.to_string(),
-                // FIXME rename the symbol and name field
-                name: "dmytro".to_string(),
-                symbol: "DMTR".to_string(),
+                name,
+                symbol,
                 icon: Some(DATA_IMAGE_SVG_NEAR_ICON.to_string()),
                 reference: None,
                 reference_hash: None,
-                decimals: 24,
+                decimals,
             },
         )
     }
@@ -50,6 +100,13 @@ impl Contract {
         let mut this = Self {
             token: FungibleToken::new(b"a".to_vec()),
             metadata: LazyOption::new(b"m".to_vec(), Some(&metadata)),
+            owner_id: owner_id.clone(),
+            failure_config: FailureConfig::default(),
+            faucet_config: FaucetConfig {
+                amount: U128(DEFAULT_FAUCET_TOKENS * 10u128.pow(metadata.decimals as u32)),
+                period: DEFAULT_FAUCET_PERIOD,
+            },
+            last_faucet_blocks: LookupMap::new(b"f".to_vec()),
         };
         this.token.internal_register_account(&owner_id);
         this.token.internal_deposit(&owner_id, total_supply.into());
@@ -58,19 +115,204 @@ impl Contract {
 
 
     pub fn mint(&mut self, account_id: AccountId, amount: U128) {
-        self.token.internal_register_account(&account_id);
+        self.assert_owner();
+        if !self.token.accounts.contains_key(&account_id) {
+            self.token.internal_register_account(&account_id);
+        }
         self.token
             .internal_deposit(&account_id, amount.into());
     }
 
     pub fn burn(&mut self, account_id: AccountId, amount: U128) {
+        self.assert_owner();
         self.token
             .internal_withdraw(&account_id, amount.into());
     }
+
+    /// Gives faucet amount of tokens to the caller once per faucet period,
+    /// the caller has to be registered with storage_deposit first
+    pub fn faucet(&mut self) -> U128 {
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.token.accounts.contains_key(&account_id),
+            "The account {} is not registered",
+            account_id
+        );
+
+        let current_block = env::block_height();
+        if let Some(last_block) = self.last_faucet_blocks.get(&account_id) {
+            assert!(
+                current_block >= last_block + self.faucet_config.period,
+                "Faucet can be used by {} again at block {}",
+                account_id,
+                last_block + self.faucet_config.period
+            );
+        }
+
+        self.last_faucet_blocks.insert(&account_id, &current_block);
+        self.token.internal_deposit(&account_id, self.faucet_config.amount.into());
+        self.faucet_config.amount
+    }
+
+    pub fn set_faucet_config(&mut self, config: FaucetConfig) {
+        self.assert_owner();
+        self.faucet_config = config;
+    }
+
+    pub fn get_faucet_config(&self) -> FaucetConfig {
+        self.faucet_config.clone()
+    }
+
+    /// Mock of wNEAR deposit, mints tokens for the attached NEAR
+    #[payable]
+    pub fn near_deposit(&mut self) {
+        let account_id = env::predecessor_account_id();
+        if !self.token.accounts.contains_key(&account_id) {
+            self.token.internal_register_account(&account_id);
+        }
+        self.token.internal_deposit(&account_id, env::attached_deposit());
+    }
+
+    /// Mock of wNEAR withdrawal, burns tokens and sends the same amount of NEAR
+    #[payable]
+    pub fn near_withdraw(&mut self, amount: U128) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        self.token.internal_withdraw(&account_id, amount.into());
+        Promise::new(account_id).transfer(amount.into())
+    }
+
+    /// Flash loan receiver hook, msg defines what is returned to the lender:
+    /// "REPAY" - principal and fee, "REPAY_PRINCIPAL" - principal only, anything else - nothing
+    pub fn on_flash_loan(&mut self, initiator_id: AccountId, amount: U128, fee: U128, msg: String) {
+        let amount: Balance = amount.into();
+        let returned: Balance = match msg.as_str() {
+            "REPAY" => amount + Balance::from(fee),
+            "REPAY_PRINCIPAL" => amount,
+            _ => 0,
+        };
+
+        if returned > 0 {
+            self.token.internal_transfer(
+                &env::current_account_id(),
+                &env::predecessor_account_id(),
+                returned,
+                Some(format!("Flash loan repayment for {}", initiator_id)),
+            );
+        }
+    }
 }
 
-// main implementation for token and storage
-near_contract_standards::impl_fungible_token_core!(Contract, token);
+impl Contract {
+    fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "This functionality is allowed to be called by admin only"
+        );
+    }
+
+    /// Consumes one of the injected transfer failures, state changes have to be kept,
+    /// so the failure is delivered by a promise which panics instead of the transfer itself
+    fn take_transfer_failure(&mut self) -> Option<Promise> {
+        if self.failure_config.fail_transfers == 0 {
+            return None;
+        }
+        assert_one_yocto();
+        self.failure_config.fail_transfers -= 1;
+        Some(Promise::new(env::current_account_id()).function_call(
+            "fail_transfer".to_string(),
+            vec![],
+            0,
+            FAIL_TRANSFER_GAS,
+        ))
+    }
+
+    /// Takes transfer fee and burned part from the sender, returns the amount left for the receiver
+    fn take_transfer_charges(&mut self, amount: Balance) -> Balance {
+        let sender_id = env::predecessor_account_id();
+        let mut fee = amount * Balance::from(self.failure_config.transfer_fee) / FAILURE_RATIO_DECIMALS;
+        let burned = amount * Balance::from(self.failure_config.burn_rate) / FAILURE_RATIO_DECIMALS;
+        if sender_id == self.owner_id {
+            fee = 0;
+        }
+
+        if fee > 0 {
+            let owner_id = self.owner_id.clone();
+            self.token.internal_transfer(&sender_id, &owner_id, fee, Some("Transfer fee".to_string()));
+        }
+        if burned > 0 {
+            self.token.internal_withdraw(&sender_id, burned);
+        }
+        amount - fee - burned
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) -> PromiseOrValue<()> {
+        if let Some(failure) = self.take_transfer_failure() {
+            return PromiseOrValue::Promise(failure);
+        }
+        let amount = self.take_transfer_charges(amount.into());
+        self.token.ft_transfer(receiver_id, U128(amount), memo);
+        PromiseOrValue::Value(())
+    }
+
+    #[payable]
+    pub fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        if let Some(failure) = self.take_transfer_failure() {
+            return PromiseOrValue::Promise(failure);
+        }
+        let amount = self.take_transfer_charges(amount.into());
+        self.token.ft_transfer_call(receiver_id, U128(amount), memo, msg)
+    }
+
+    pub fn ft_total_supply(&self) -> U128 {
+        self.token.ft_total_supply()
+    }
+
+    pub fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        assert!(!self.failure_config.fail_balance_of, "ft_balance_of failed by failure injection");
+        self.token.ft_balance_of(account_id)
+    }
+
+    #[private]
+    pub fn fail_transfer(&self) {
+        env::panic_str("Transfer failed by failure injection");
+    }
+
+    pub fn set_failure_config(&mut self, config: FailureConfig) {
+        self.assert_owner();
+        assert!(
+            Balance::from(config.transfer_fee) + Balance::from(config.burn_rate) <= FAILURE_RATIO_DECIMALS,
+            "Transfer fee and burn rate should be less or equal to 100% in total"
+        );
+        self.failure_config = config;
+    }
+
+    pub fn get_failure_config(&self) -> FailureConfig {
+        self.failure_config.clone()
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenResolver for Contract {
+    #[private]
+    fn ft_resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> U128 {
+        let (used_amount, _) = self.token.internal_ft_resolve_transfer(&sender_id, receiver_id, amount);
+        used_amount.into()
+    }
+}
+
+// main implementation for storage
 near_contract_standards::impl_fungible_token_storage!(Contract, token);
 
 #[near_bindgen]
@@ -94,7 +336,8 @@ mod tests {
 
     fn init() -> (VMContextBuilder, AccountId, Contract) {
         // get VM builer
-        let context = VMContextBuilder::new();
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(alice());
 
         // account for contract
         let _contract_account = alice();
@@ -155,6 +398,43 @@ mod tests {
         assert_eq!(contract.ft_balance_of(bob_account), 0.into())
     }
 
+    #[test]
+    fn test_on_flash_loan() {
+        let (mut context, contract_account, mut contract) = init();
+
+        testing_env!(context.build());
+
+        let lender_account = bob();
+        contract.mint(lender_account.clone(), 0.into());
+        testing_env!(context.predecessor_account_id(lender_account.clone()).build());
+
+        contract.on_flash_loan(accounts(2), 100.into(), 1.into(), "REPAY".to_string());
+        assert_eq!(contract.ft_balance_of(lender_account.clone()), 101.into());
+
+        contract.on_flash_loan(accounts(2), 100.into(), 1.into(), "REPAY_PRINCIPAL".to_string());
+        assert_eq!(contract.ft_balance_of(lender_account.clone()), 201.into());
+
+        contract.on_flash_loan(accounts(2), 100.into(), 1.into(), "KEEP".to_string());
+        assert_eq!(contract.ft_balance_of(lender_account), 201.into());
+        assert_eq!(contract.ft_balance_of(contract_account), (TOTAL_SUPPLY - 201).into());
+    }
+
+    #[test]
+    fn test_near_deposit_and_withdraw() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(2), TOTAL_SUPPLY.into());
+
+        testing_env!(context.attached_deposit(1000).build());
+        contract.near_deposit();
+        assert_eq!(contract.ft_balance_of(accounts(1)), 1000.into());
+
+        testing_env!(context.attached_deposit(1).build());
+        contract.near_withdraw(400.into());
+        assert_eq!(contract.ft_balance_of(accounts(1)), 600.into());
+        assert_eq!(contract.ft_total_supply(), (TOTAL_SUPPLY + 600).into());
+    }
+
     #[test]
     fn test_transfer() {
         let mut context = get_context(accounts(2));
@@ -191,4 +471,123 @@ mod tests {
         assert_eq!(contract.ft_balance_of(accounts(1)).0, transferred_tokens);
     }
 
+    fn init_transfer(context: &mut VMContextBuilder) -> Contract {
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let mut contract = Contract::new_default_meta(accounts(2), TOTAL_SUPPLY.into());
+        contract.mint(accounts(1), 1000.into());
+        contract.mint(accounts(3), 0.into());
+        contract
+    }
+
+    #[test]
+    fn test_failed_transfers() {
+        let mut context = get_context(accounts(2));
+        let mut contract = init_transfer(&mut context);
+        contract.set_failure_config(FailureConfig { fail_transfers: 1, ..Default::default() });
+
+        testing_env!(context.attached_deposit(1).predecessor_account_id(accounts(1)).build());
+        contract.ft_transfer(accounts(3), 100.into(), None);
+        assert_eq!(contract.ft_balance_of(accounts(1)), 1000.into());
+        assert_eq!(contract.get_failure_config().fail_transfers, 0);
+
+        contract.ft_transfer(accounts(3), 100.into(), None);
+        assert_eq!(contract.ft_balance_of(accounts(3)), 100.into());
+    }
+
+    #[test]
+    fn test_transfer_fee_and_burn() {
+        let mut context = get_context(accounts(2));
+        let mut contract = init_transfer(&mut context);
+        contract.set_failure_config(FailureConfig {
+            transfer_fee: 1000.into(),
+            burn_rate: 500.into(),
+            ..Default::default()
+        });
+
+        testing_env!(context.attached_deposit(1).predecessor_account_id(accounts(1)).build());
+        contract.ft_transfer(accounts(3), 100.into(), None);
+        assert_eq!(contract.ft_balance_of(accounts(1)), 900.into());
+        assert_eq!(contract.ft_balance_of(accounts(3)), 85.into());
+        assert_eq!(contract.ft_balance_of(accounts(2)), (TOTAL_SUPPLY + 10).into());
+        assert_eq!(contract.ft_total_supply(), (TOTAL_SUPPLY + 1000 - 5).into());
+    }
+
+    #[test]
+    #[should_panic(expected = "ft_balance_of failed by failure injection")]
+    fn failed_balance_of() {
+        let mut context = get_context(accounts(2));
+        let mut contract = init_transfer(&mut context);
+        contract.set_failure_config(FailureConfig { fail_balance_of: true, ..Default::default() });
+
+        contract.ft_balance_of(accounts(1));
+    }
+
+    #[test]
+    fn test_new_with_meta() {
+        testing_env!(get_context(accounts(2)).build());
+        let contract = Contract::new_with_meta(accounts(2), TOTAL_SUPPLY.into(), "USD Coin".to_string(), "USDC".to_string(), 6);
+
+        let metadata = contract.ft_metadata();
+        assert_eq!(metadata.symbol, "USDC");
+        assert_eq!(metadata.decimals, 6);
+        assert_eq!(contract.get_faucet_config().amount, U128(1000 * 10u128.pow(6)));
+    }
+
+    #[test]
+    fn test_faucet_rate_limit() {
+        let mut context = get_context(accounts(2));
+        let mut contract = init_transfer(&mut context);
+        contract.set_faucet_config(FaucetConfig { amount: 100.into(), period: 10 });
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_index(5).build());
+        assert_eq!(contract.faucet(), 100.into());
+
+        testing_env!(context.block_index(15).build());
+        contract.faucet();
+        assert_eq!(contract.ft_balance_of(accounts(3)), 200.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Faucet can be used by")]
+    fn failed_faucet_too_often() {
+        let mut context = get_context(accounts(2));
+        let mut contract = init_transfer(&mut context);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_index(5).build());
+        contract.faucet();
+
+        testing_env!(context.block_index(6).build());
+        contract.faucet();
+    }
+
+    #[test]
+    #[should_panic(expected = "is not registered")]
+    fn failed_faucet_not_registered() {
+        let mut context = get_context(accounts(2));
+        let mut contract = init_transfer(&mut context);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.faucet();
+    }
+
+    #[test]
+    #[should_panic(expected = "This functionality is allowed to be called by admin only")]
+    fn failed_mint_not_owner() {
+        let mut context = get_context(accounts(2));
+        let mut contract = init_transfer(&mut context);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.mint(accounts(1), 1000.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "This functionality is allowed to be called by admin only")]
+    fn failed_set_failure_config_not_owner() {
+        let mut context = get_context(accounts(2));
+        let mut contract = init_transfer(&mut context);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_failure_config(FailureConfig::default());
+    }
+
 }